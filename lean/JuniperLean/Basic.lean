@@ -1,19 +1,20 @@
 import Mathlib
 import JuniperLean.JuniperJson
 
-attribute [juniper_json] Rat.add_zero
-attribute [juniper_json] Rat.add_comm
-attribute [juniper_json] Rat.add_assoc
-attribute [juniper_json] Rat.add_mul
-attribute [juniper_json] Rat.neg_add_cancel
-attribute [juniper_json] Rat.mul_assoc
-attribute [juniper_json] Rat.mul_comm
-attribute [juniper_json] Rat.mul_one
-attribute [juniper_json] Rat.mul_zero
-attribute [juniper_json] Rat.mul_add
+-- `field` marks the ℚ field identities, which Juniper also applies to real terms
+attribute [juniper_json field] Rat.add_zero
+attribute [juniper_json field] Rat.add_comm
+attribute [juniper_json field] Rat.add_assoc
+attribute [juniper_json field] Rat.add_mul
+attribute [juniper_json field] Rat.neg_add_cancel
+attribute [juniper_json field] Rat.mul_assoc
+attribute [juniper_json field] Rat.mul_comm
+attribute [juniper_json field] Rat.mul_one
+attribute [juniper_json field] Rat.mul_zero
+attribute [juniper_json field] Rat.mul_add
 -- attribute [juniper_json] Rat.mul_inv_cancel -- Ne causes soundness issues (e.g. inv 0 != 0)
-attribute [juniper_json] Rat.sub_eq_add_neg
-attribute [juniper_json] Rat.inv_neg
+attribute [juniper_json field] Rat.sub_eq_add_neg
+attribute [juniper_json field] Rat.inv_neg
 attribute [juniper_json] Real.cos_add
 attribute [juniper_json] Real.cos_neg
 attribute [juniper_json] Real.cos_pi
@@ -75,21 +76,26 @@ attribute [juniper_json] Real.two_mul_sin_mul_sin
 attribute [juniper_json] Real.one_rpow
 attribute [juniper_json] Real.rpow_one
 attribute [juniper_json] Real.rpow_zero
+attribute [juniper_json] Real.rpow_natCast
 
-@[juniper_json]
+@[juniper_json field]
 theorem involutive_neg_neg (a : ℚ) : -(-a) = a := by
   exact InvolutiveNeg.neg_neg a
 
-@[juniper_json]
+@[juniper_json field]
 theorem involutive_inv_inv (a : ℚ) : a⁻¹⁻¹ = a := by
   exact InvolutiveInv.inv_inv a
 
-@[juniper_json]
+@[juniper_json field]
 theorem add_neg_neg (a b : ℚ) : (-a) + (-b) = -(a + b) := by
   linarith
 
-@[juniper_json]
+@[juniper_json field]
 theorem mul_self (a : ℚ) : a * a = a ^ 2 := by
   linarith
 
+@[juniper_json]
+theorem real_natCast_add (m n : ℕ) : ((m + n : ℕ) : ℝ) = m + n := by
+  exact Nat.cast_add m n
+
 #save_juniper_json "../exported.json"
@@ -0,0 +1,19 @@
+import JuniperLean.JuniperJson
+
+-- The statements behind rs/test_assets/add_zero_{let,beta,proj}.json, each `a + 0 = a` with its lhs
+-- hidden by an elaboration artifact. This file isn't part of the library (so they stay out of
+-- exported.json); run `lake env lean JuniperLean/Fixtures.lean` and copy each logged type into its
+-- fixture.
+
+theorem add_zero_let (a : ℚ) : (let z : ℚ := 0; a + z) = a := by
+  simp
+
+theorem add_zero_beta (a : ℚ) : (fun x : ℚ => x + 0) a = a := by
+  simp
+
+theorem add_zero_proj (a : ℚ) : (instHAdd : HAdd ℚ ℚ ℚ).1 a 0 = a := by
+  exact add_zero a
+
+#show_type_json add_zero_let
+#show_type_json add_zero_beta
+#show_type_json add_zero_proj
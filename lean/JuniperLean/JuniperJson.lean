@@ -23,9 +23,11 @@ elab "#show_type_json " t:term : command => Command.runTermElabM fun _ => do
   let typ ← inferType e
   logInfo m!"{ToJson.toJson typ}"
 
+-- `field` marks a ℚ theorem as a field identity, which holds just as well over ℝ
 structure JuniperJsonEntry where
   name: Name
   type: Expr
+  field: Bool := false
 deriving ToJson
 
 initialize juniperJsonExtension : SimplePersistentEnvExtension JuniperJsonEntry (Array JuniperJsonEntry) ←
@@ -38,13 +40,18 @@ initialize juniperJsonExtension : SimplePersistentEnvExtension JuniperJsonEntry
 initialize juniperJsonAttr : Unit ←
   registerBuiltinAttribute {
     name := `juniper_json
-    descr := "adds a theorem to the rewrite conversion json file"
+    descr := "adds a theorem to the rewrite conversion json file (`juniper_json field` for field identities)"
     applicationTime := AttributeApplicationTime.afterCompilation
     add := fun declName stx _attrKind => do
+      let field ← match stx[1].getOptional? with
+        | none => pure false
+        | some arg =>
+          if arg.getId == `field then pure true
+          else throwError "unknown juniper_json option {arg}, expected `field`"
       let typ ← MetaM.run' do
         let typ ← inferType (Expr.const declName [])
         return typ
-      let entry ← pure <| ⟨declName, typ⟩
+      let entry ← pure <| ⟨declName, typ, field⟩
 
       modifyEnv fun env =>
         juniperJsonExtension.addEntry env entry
@@ -1,20 +1,120 @@
 use std::fmt::Display;
 
-use anyhow::{Error, Result};
+use anyhow::{Context, Error, Result};
 
-use egg::{Condition, ConditionEqual, ConditionalApplier, EGraph, Id, Pattern, Rewrite, Subst};
-use juniper_math_expression::{ConstantFold, MathExpression};
-use lean_parse::lean_expr::{LeanExpr, Literal, Name};
+use egg::{
+    Condition, ConditionEqual, ConditionalApplier, EGraph, Id, Pattern, Rewrite, Subst, Var,
+};
+use juniper_math_expression::{ConstantFold, MathExpression, Sort};
+use lean_parse::lean_expr::{FVarId, LeanExpr, Literal, Name};
 use num::BigInt;
 use serde::{Deserialize, Serialize};
 
+mod normalize;
+mod report;
+pub use report::{ConversionFailure, ConversionReport, ConversionStage, TheoremReport};
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct JuniperJsonEntry {
     name: Name,
     #[serde(rename = "type")]
     typ: LeanExpr,
+    // whether the theorem is marked (with `@[juniper_json field]`) as a field identity, which
+    // sort embeddings may generalize
+    #[serde(default)]
+    field: bool,
+}
+
+impl JuniperJsonEntry {
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+}
+
+// a Lean constant which the transpiler has no interpretation for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownConstantError {
+    pub constant: Name,
 }
 
+impl Display for UnknownConstantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown constant: {}", self.constant)
+    }
+}
+
+impl std::error::Error for UnknownConstantError {}
+
+// a theorem whose two sides are the same pattern, which would only produce rules rewriting terms
+// to themselves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrivialTheoremError {
+    pub pattern: String,
+}
+
+impl Display for TrivialTheoremError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "both sides are {}", self.pattern)
+    }
+}
+
+impl std::error::Error for TrivialTheoremError {}
+
+// a theorem left out of a lenient conversion, along with the constant that caused it
+pub type SkippedTheorem = (Name, UnknownConstantError);
+
+// the LeanExpr at which a conversion error originated, attached as context to that error
+#[derive(Debug, Clone)]
+pub struct OffendingSubterm(pub LeanExpr);
+
+impl Display for OffendingSubterm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at subterm: {}", self.0.pretty())
+    }
+}
+
+// declares that rules proven over the `from` sort may also be applied to terms of any sort up to
+// `to` (e.g. Rat field identities which hold just as well over Real)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortEmbedding {
+    pub from: Sort,
+    pub to: Sort,
+    // whether the embedding only holds for the theorems the export marks as field identities,
+    // rather than every theorem over `from`
+    pub field_only: bool,
+}
+
+impl SortEmbedding {
+    fn applies_to(&self, field: bool) -> bool {
+        field || !self.field_only
+    }
+}
+
+// the largest sort a rule proven over the given sort may be applied to
+fn embed(sort: Sort, embeddings: &[SortEmbedding]) -> Sort {
+    embeddings
+        .iter()
+        .filter(|embedding| embedding.from == sort)
+        .fold(sort, |bound, embedding| bound.max(embedding.to))
+}
+
+// the Juniper sort corresponding to a Lean carrier type name
+fn carrier_sort(name: &str) -> Option<Sort> {
+    match name {
+        "Nat" => Some(Sort::Nat),
+        "Int" => Some(Sort::Int),
+        "Rat" => Some(Sort::Rat),
+        "Real" => Some(Sort::Real),
+        _ => None,
+    }
+}
+
+type MathRewrite = Rewrite<MathExpression, ConstantFold>;
+
+// the rewrite condition closures generated from theorem hypotheses
+type JuniperCondition =
+    Box<dyn Fn(&mut EGraph<MathExpression, ConstantFold>, Id, &Subst) -> bool + Send + Sync>;
+
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 struct Hole;
 
@@ -34,12 +134,12 @@ impl Display for LMEIntermediateDefinedConst {
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 enum LMEIntermediateConst {
     OfNat {
-        out_type: Option<Name>,
+        out_type: Option<String>,
         val: Option<BigInt>,
         inst: Option<Hole>,
     },
     OfScientific {
-        out_type: Option<Name>,
+        out_type: Option<String>,
         inst: Option<Hole>,
         mantissa: Option<BigInt>,
         exponent_sign: Option<bool>,
@@ -93,36 +193,37 @@ impl Display for LMEIntermediateConst {
 // which is partially instantiable
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 enum LMEIntermediateRep {
+    Type(String),
     DefinedConst(LMEIntermediateDefinedConst),
     Const(LMEIntermediateConst),
-    Var(Name),
+    Var(String),
     Forall {
-        binder_name: Option<Name>,
+        binder_name: Option<String>,
         binder_type: Option<Box<Self>>,
         body: Option<Box<Self>>,
     },
     Eq {
-        all_type: Option<Name>,
+        all_type: Option<String>,
         in1: Option<Box<Self>>,
         in2: Option<Box<Self>>,
     },
     Ne {
-        all_type: Option<Name>,
+        all_type: Option<String>,
         in1: Option<Box<Self>>,
         in2: Option<Box<Self>>,
     },
     HBool {
         operator: Option<String>,
-        in1_type: Option<Name>,
-        in2_type: Option<Name>,
-        out_type: Option<Name>,
+        in1_type: Option<String>,
+        in2_type: Option<String>,
+        out_type: Option<String>,
         inst: Option<Hole>,
         in1: Option<Box<Self>>,
         in2: Option<Box<Self>>,
     },
     TUnary {
         operator: Option<String>,
-        all_type: Option<Name>,
+        all_type: Option<String>,
         inst: Option<Hole>,
         in1: Option<Box<Self>>,
     },
@@ -130,11 +231,20 @@ enum LMEIntermediateRep {
         operator: Option<String>,
         in1: Option<Box<Self>>,
     },
+    // coercions (↑x), which become cast nodes: their arguments keep the sort they're cast from,
+    // so cast lemmas only apply to terms of that sort
+    Cast {
+        in_type: Option<String>,
+        out_type: Option<String>,
+        inst: Option<Hole>,
+        in1: Option<Box<Self>>,
+    },
 }
 
 impl Display for LMEIntermediateRep {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Type(name) => write!(f, "{name}"),
             Self::DefinedConst(dc) => write!(f, "{dc}"),
             Self::Const(c) => write!(f, "{c}"),
             Self::Var(name) => write!(f, "?{name}"),
@@ -195,6 +305,13 @@ impl Display for LMEIntermediateRep {
                     write!(f, "")
                 }
             }
+            Self::Cast { in1, .. } => {
+                if let Some(in1) = in1 {
+                    write!(f, "(cast {in1})")
+                } else {
+                    write!(f, "")
+                }
+            }
         }
     }
 }
@@ -202,7 +319,7 @@ impl Display for LMEIntermediateRep {
 impl LMEIntermediateRep {
     // convert a declName to an uninstantiated intermediate representation
     fn name_to_ir(name: Name) -> Result<Self> {
-        match name.as_str() {
+        match name.to_string().as_str() {
             "Real.pi" => Ok(Self::DefinedConst(LMEIntermediateDefinedConst::Pi)),
             "OfScientific.ofScientific" => Ok(Self::Const(LMEIntermediateConst::OfScientific {
                 out_type: None,
@@ -295,26 +412,54 @@ impl LMEIntermediateRep {
                 operator: Some("sqrt".to_string()),
                 in1: None,
             }),
-            _ => Err(Error::msg(format!("unknown name: {}", name))),
+            "Nat.cast" => Ok(Self::Cast {
+                in_type: Some("Nat".to_string()),
+                out_type: None,
+                inst: None,
+                in1: None,
+            }),
+            "Int.cast" => Ok(Self::Cast {
+                in_type: Some("Int".to_string()),
+                out_type: None,
+                inst: None,
+                in1: None,
+            }),
+            "Rat.cast" => Ok(Self::Cast {
+                in_type: Some("Rat".to_string()),
+                out_type: None,
+                inst: None,
+                in1: None,
+            }),
+            _ => Err(UnknownConstantError { constant: name }.into()),
+        }
+    }
+
+    // convert a bare (unapplied) constant to an intermediate representation, which is either a
+    // known carrier type or a complete name_to_ir result
+    fn const_to_ir(name: Name) -> Result<Self> {
+        if carrier_sort(&name.to_string()).is_some() {
+            Ok(Self::Type(name.to_string()))
+        } else {
+            Self::name_to_ir(name)
         }
     }
 
     // parse the type components of LeanExprs into Names
-    fn type_parse(arg: LeanExpr, _de_bruijn_names: Vec<Name>) -> Result<Name> {
+    fn type_parse(arg: LeanExpr) -> Result<String> {
         match arg {
-            LeanExpr::Const { decl_name, .. } => Ok(decl_name),
-            _ => Err(Error::msg(format!("bad type: {}", arg))),
+            LeanExpr::Const { decl_name, .. } => Ok(decl_name.to_string()),
+            _ => Err(Error::msg("bad type").context(OffendingSubterm(arg))),
         }
     }
 
     // transitions the partial instantiation to include the next apply argument
-    fn app_state_next(current: Self, arg: LeanExpr, de_bruijn_names: Vec<Name>) -> Result<Self> {
+    fn app_state_next(current: Self, arg: LeanExpr) -> Result<Self> {
         // because the arguments are ordered, we only have to specify that one argument is None
         // also this is really ugly, but that's mostly bc rust enum structs lack default support lol
         Ok(match current {
             Self::Const(LMEIntermediateConst::OfNat { out_type: None, .. }) => {
                 Self::Const(LMEIntermediateConst::OfNat {
-                    out_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                    out_type: Some(Self::type_parse(arg)?),
                     val: None,
                     inst: None,
                 })
@@ -326,8 +471,8 @@ impl LMEIntermediateRep {
             }) => Self::Const(LMEIntermediateConst::OfNat {
                 out_type,
                 val: match arg {
-                    LeanExpr::Lit(Literal::NatVal { val }) => Some(val.into()),
-                    _ => return Err(Error::msg(format!("bad OfNat val: {}", arg))),
+                    LeanExpr::Lit(Literal::NatVal { val }) => Some(val),
+                    _ => return Err(Error::msg("bad OfNat val").context(OffendingSubterm(arg))),
                 },
                 inst: None,
             }),
@@ -342,7 +487,7 @@ impl LMEIntermediateRep {
             }),
             Self::Const(LMEIntermediateConst::OfScientific { out_type: None, .. }) => {
                 Self::Const(LMEIntermediateConst::OfScientific {
-                    out_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                    out_type: Some(Self::type_parse(arg)?),
                     inst: None,
                     mantissa: None,
                     exponent_sign: None,
@@ -369,8 +514,8 @@ impl LMEIntermediateRep {
                 out_type,
                 inst,
                 mantissa: match arg {
-                    LeanExpr::Lit(Literal::NatVal { val }) => Some(val.into()),
-                    _ => return Err(Error::msg(format!("bad mantissa: {}", arg))),
+                    LeanExpr::Lit(Literal::NatVal { val }) => Some(val),
+                    _ => return Err(Error::msg("bad mantissa").context(OffendingSubterm(arg))),
                 },
                 exponent_sign: None,
                 decimal_exponent: None,
@@ -386,17 +531,19 @@ impl LMEIntermediateRep {
                 inst,
                 mantissa,
                 exponent_sign: match arg {
-                    LeanExpr::Const { decl_name, .. } => match decl_name.as_str() {
+                    LeanExpr::Const { ref decl_name, .. } => match decl_name.to_string().as_str() {
                         "Bool.true" => Some(true),
                         "Bool.false" => Some(false),
                         _ => {
-                            return Err(Error::msg(format!(
-                                "bad exponent_sign const: {}",
-                                decl_name
-                            )))
+                            return Err(Error::msg("bad exponent_sign const")
+                                .context(OffendingSubterm(arg)))
                         }
                     },
-                    _ => return Err(Error::msg(format!("bad exponent_sign arg: {}", arg))),
+                    _ => {
+                        return Err(
+                            Error::msg("bad exponent_sign arg").context(OffendingSubterm(arg))
+                        )
+                    }
                 },
                 decimal_exponent: None,
             }),
@@ -413,11 +560,15 @@ impl LMEIntermediateRep {
                 exponent_sign,
                 decimal_exponent: match arg {
                     LeanExpr::Lit(Literal::NatVal { val }) => Some(val.try_into()?),
-                    _ => return Err(Error::msg(format!("bad decimal_exponent: {}", arg))),
+                    _ => {
+                        return Err(
+                            Error::msg("bad decimal_exponent").context(OffendingSubterm(arg))
+                        )
+                    }
                 },
             }),
             Self::Eq { all_type: None, .. } => Self::Eq {
-                all_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                all_type: Some(Self::type_parse(arg)?),
                 in1: None,
                 in2: None,
             },
@@ -427,10 +578,7 @@ impl LMEIntermediateRep {
                 ..
             } => Self::Eq {
                 all_type,
-                in1: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
                 in2: None,
             },
             Self::Eq {
@@ -440,13 +588,10 @@ impl LMEIntermediateRep {
             } => Self::Eq {
                 all_type,
                 in1,
-                in2: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in2: Some(Box::new(Self::from_lean_recursive(arg)?)),
             },
             Self::Ne { all_type: None, .. } => Self::Ne {
-                all_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                all_type: Some(Self::type_parse(arg)?),
                 in1: None,
                 in2: None,
             },
@@ -456,10 +601,7 @@ impl LMEIntermediateRep {
                 ..
             } => Self::Ne {
                 all_type,
-                in1: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
                 in2: None,
             },
             Self::Ne {
@@ -469,10 +611,7 @@ impl LMEIntermediateRep {
             } => Self::Ne {
                 all_type,
                 in1,
-                in2: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in2: Some(Box::new(Self::from_lean_recursive(arg)?)),
             },
             Self::HBool {
                 operator,
@@ -480,7 +619,7 @@ impl LMEIntermediateRep {
                 ..
             } => Self::HBool {
                 operator,
-                in1_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                in1_type: Some(Self::type_parse(arg)?),
                 in2_type: None,
                 out_type: None,
                 inst: None,
@@ -495,7 +634,7 @@ impl LMEIntermediateRep {
             } => Self::HBool {
                 operator,
                 in1_type,
-                in2_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                in2_type: Some(Self::type_parse(arg)?),
                 out_type: None,
                 inst: None,
                 in1: None,
@@ -511,7 +650,7 @@ impl LMEIntermediateRep {
                 operator,
                 in1_type,
                 in2_type,
-                out_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                out_type: Some(Self::type_parse(arg)?),
                 inst: None,
                 in1: None,
                 in2: None,
@@ -546,10 +685,7 @@ impl LMEIntermediateRep {
                 in2_type,
                 out_type,
                 inst,
-                in1: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
                 in2: None,
             },
             Self::HBool {
@@ -567,10 +703,7 @@ impl LMEIntermediateRep {
                 out_type,
                 inst,
                 in1,
-                in2: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in2: Some(Box::new(Self::from_lean_recursive(arg)?)),
             },
             Self::TUnary {
                 operator,
@@ -578,7 +711,7 @@ impl LMEIntermediateRep {
                 ..
             } => Self::TUnary {
                 operator,
-                all_type: Some(Self::type_parse(arg, de_bruijn_names.clone())?),
+                all_type: Some(Self::type_parse(arg)?),
                 inst: None,
                 in1: None,
             },
@@ -602,56 +735,92 @@ impl LMEIntermediateRep {
                 operator,
                 all_type,
                 inst,
-                in1: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
             },
             Self::IUnary {
                 operator,
                 in1: None,
             } => Self::IUnary {
                 operator,
-                in1: Some(Box::new(Self::from_lean_recursive(
-                    arg,
-                    de_bruijn_names.clone(),
-                )?)),
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
+            },
+            Self::Cast {
+                in_type,
+                out_type: None,
+                ..
+            } => {
+                let out_type = Self::type_parse(arg.clone())?;
+                // a cast is only the identity on values if it's an embedding of one sort into another
+                match (
+                    in_type.as_deref().and_then(carrier_sort),
+                    carrier_sort(&out_type),
+                ) {
+                    (Some(from), Some(to)) if from <= to => Self::Cast {
+                        in_type,
+                        out_type: Some(out_type),
+                        inst: None,
+                        in1: None,
+                    },
+                    _ => {
+                        return Err(Error::msg(format!(
+                            "cast from {in_type:?} to {out_type} is not an embedding"
+                        ))
+                        .context(OffendingSubterm(arg)))
+                    }
+                }
+            }
+            Self::Cast {
+                in_type,
+                out_type,
+                inst: None,
+                ..
+            } => Self::Cast {
+                in_type,
+                out_type,
+                inst: Some(Hole),
+                in1: None,
+            },
+            Self::Cast {
+                in_type,
+                out_type,
+                inst,
+                in1: None,
+            } => Self::Cast {
+                in_type,
+                out_type,
+                inst,
+                in1: Some(Box::new(Self::from_lean_recursive(arg)?)),
             },
             _ => {
                 return Err(Error::msg(format!(
-                    "unimplemented or already complete apply found: {:?} (with arg {})",
-                    current, arg
-                )))
+                    "unimplemented or already complete apply found: {:?}",
+                    current
+                ))
+                .context(OffendingSubterm(arg)))
             }
         })
     }
 
     // parses LeanExpr::App into an intermediate representation
-    fn app_parse(
-        function: Box<LeanExpr>,
-        arg: Box<LeanExpr>,
-        de_bruijn_names: Vec<Name>,
-    ) -> Result<Self> {
-        match *function {
-            // recursive case: App
-            LeanExpr::App {
-                function: function_new,
-                arg: arg_new,
-            } => {
-                let downstream = Self::app_parse(function_new, arg_new, de_bruijn_names.clone())?;
-                Self::app_state_next(downstream, *arg, de_bruijn_names)
-            }
-            // base case: Const
-            LeanExpr::Const { decl_name, .. } => {
-                // we want to add the first argument to the new instance
-                Self::app_state_next(Self::name_to_ir(decl_name)?, *arg, de_bruijn_names)
-            }
-            _ => Err(Error::msg(format!("unknown apply component: {}", function))),
-        }
+    fn app_parse(expr: LeanExpr) -> Result<Self> {
+        let LeanExpr::Const { decl_name, .. } = expr.get_app_fn() else {
+            return Err(Error::msg("unknown apply component")
+                .context(OffendingSubterm(expr.get_app_fn().clone())));
+        };
+        let ir = Self::name_to_ir(decl_name.clone())
+            .context(OffendingSubterm(expr.get_app_fn().clone()))?;
+
+        // feed the arguments into the new instance one at a time
+        expr.get_app_args()
+            .into_iter()
+            .try_fold(ir, |current, arg| {
+                Self::app_state_next(current, arg.clone())
+            })
     }
 
-    // recursively convert LeanExprs into intermediate representations
-    fn from_lean_recursive(expr: LeanExpr, de_bruijn_names: Vec<Name>) -> Result<Self> {
+    // convert LeanExprs into intermediate representations, where bound variables have already
+    // been instantiated with free variables named after their binders
+    fn from_lean_recursive(expr: LeanExpr) -> Result<Self> {
         match expr {
             LeanExpr::ForallE {
                 binder_name,
@@ -659,67 +828,62 @@ impl LMEIntermediateRep {
                 body,
                 binder_info: _,
             } => Ok(Self::Forall {
-                binder_name: Some(binder_name.clone()),
-                binder_type: Some(Box::new(Self::from_lean_recursive(
-                    *binder_type,
-                    de_bruijn_names.clone(),
-                )?)),
-                body: Some(Box::new(Self::from_lean_recursive(*body, {
-                    let mut new_names = de_bruijn_names.clone();
-                    new_names.push(binder_name);
-                    new_names
-                })?)),
+                binder_name: Some(binder_name.to_string()),
+                binder_type: Some(Box::new(Self::from_lean_recursive(*binder_type)?)),
+                body: Some(Box::new(Self::from_lean_recursive(body.instantiate1(
+                    &LeanExpr::FVar {
+                        fvar_id: FVarId { name: binder_name },
+                    },
+                ))?)),
             }),
-            LeanExpr::App { function, arg } => Self::app_parse(function, arg, de_bruijn_names),
-            LeanExpr::BVar { de_bruijn_index } => Ok(Self::Var(
-                if let Some(name) =
-                    de_bruijn_names.get(de_bruijn_names.len() - 1 - de_bruijn_index as usize)
-                {
-                    name.clone()
-                } else {
-                    return Err(Error::msg(format!(
-                        "bad deBruijn index {} for names: {:?}",
-                        de_bruijn_index,
-                        de_bruijn_names.clone()
-                    )));
-                },
-            )),
-            LeanExpr::Const { decl_name, .. } => {
-                if let Ok(ir) = Self::name_to_ir(decl_name) {
-                    Ok(ir)
-                } else {
-                    Ok(Self::DefinedConst(LMEIntermediateDefinedConst::Pi))
-                }
+            LeanExpr::App { .. } => Self::app_parse(expr),
+            LeanExpr::FVar { fvar_id } => Ok(Self::Var(fvar_id.name.to_string())),
+            LeanExpr::BVar { .. } => {
+                Err(Error::msg("loose bound variable").context(OffendingSubterm(expr)))
+            }
+            LeanExpr::Const { ref decl_name, .. } => {
+                Self::const_to_ir(decl_name.clone()).context(OffendingSubterm(expr))
             }
-            _ => Err(Error::msg(format!(
-                "improper top-level structure: {}",
-                expr
-            ))),
+            _ => Err(Error::msg("improper top-level structure").context(OffendingSubterm(expr))),
         }
     }
 
     // convert LeanExpr into an intermediate representation
     fn from_lean(expr: LeanExpr) -> Result<Self> {
-        Self::from_lean_recursive(expr, Vec::new())
+        Self::from_lean_recursive(normalize::normalize(expr))
     }
 
-    // split apart intermediate representations at their top-level Eq (collecting condition Foralls and ignoring others)
-    fn split_at_top_eq(&self, conditions: Vec<Self>) -> Option<(Vec<Self>, Self, Self)> {
+    // split apart intermediate representations at their top-level Eq (collecting condition Foralls
+    // and the types of variable Foralls)
+    fn split_at_top_eq(
+        &self,
+        conditions: Vec<Self>,
+        variable_types: Vec<(String, String)>,
+    ) -> Option<SplitTheorem> {
         match self {
             Self::Forall {
-                body, binder_type, ..
+                binder_name,
+                body,
+                binder_type,
             } => {
                 if let Some(body) = body {
                     if let Some(binder_type) = binder_type {
                         match *binder_type.clone() {
-                            Self::Const(_) | Self::DefinedConst(_) => {
-                                body.split_at_top_eq(conditions)
-                            }
-                            b => body.split_at_top_eq({
-                                let mut new_conditions = conditions.clone();
-                                new_conditions.push(b);
-                                new_conditions
+                            Self::Type(typ) => body.split_at_top_eq(conditions, {
+                                let mut new_variable_types = variable_types.clone();
+                                if let Some(binder_name) = binder_name {
+                                    new_variable_types.push((binder_name.clone(), typ));
+                                }
+                                new_variable_types
                             }),
+                            b => body.split_at_top_eq(
+                                {
+                                    let mut new_conditions = conditions.clone();
+                                    new_conditions.push(b);
+                                    new_conditions
+                                },
+                                variable_types,
+                            ),
                         }
                     } else {
                         None
@@ -728,13 +892,15 @@ impl LMEIntermediateRep {
                     None
                 }
             }
-            Self::Eq { in1, in2, .. } => {
+            Self::Eq { all_type, in1, in2 } => {
                 if let Some(in1) = in1 {
-                    if let Some(in2) = in2 {
-                        Some((conditions, (**in1).clone(), (**in2).clone()))
-                    } else {
-                        None
-                    }
+                    in2.as_ref().map(|in2| SplitTheorem {
+                        conditions,
+                        variable_types,
+                        carrier: all_type.clone(),
+                        lhs: (**in1).clone(),
+                        rhs: (**in2).clone(),
+                    })
                 } else {
                     None
                 }
@@ -743,27 +909,131 @@ impl LMEIntermediateRep {
         }
     }
 
-    fn as_condition(
-        self,
-    ) -> Result<
-        Box<dyn Fn(&mut EGraph<MathExpression, ConstantFold>, Id, &Subst) -> bool + Send + Sync>,
-    > {
+    fn into_rule_condition(self) -> Result<RuleCondition> {
+        match self {
+            Self::Eq { in1: Some(in1), in2: Some(in2), .. } => Ok(RuleCondition::Equal(
+                in1.to_math_expression()?.to_string(),
+                in2.to_math_expression()?.to_string(),
+            )),
+            Self::Ne { in1: Some(in1), in2: Some(in2), .. } => Ok(RuleCondition::NotEqual(
+                in1.to_math_expression()?.to_string(),
+                in2.to_math_expression()?.to_string(),
+            )),
+            _ => Err(Error::msg(format!("Self::into_rule_condition could not successfully convert the condition {self} into a rule condition"))),
+        }
+    }
+
+    // convert intermediate representations into MathExpression Patterns
+    // the first operator applied at a type where it truncates or floors, along with that type's
+    // sort. A theorem using one can't be applied with Juniper's operators, which are those of the
+    // reals
+    fn truncating_operator(&self) -> Option<(String, Sort)> {
+        let truncates = |operator: &Option<String>, typ: &Option<String>| {
+            let sort = typ.as_deref().and_then(carrier_sort)?;
+            match (operator.as_deref()?, sort) {
+                (operator @ "-", Sort::Nat) | (operator @ ("/" | "inv"), Sort::Nat | Sort::Int) => {
+                    Some((operator.to_string(), sort))
+                }
+                _ => None,
+            }
+        };
+        let child = |child: &Option<Box<Self>>| child.as_ref()?.truncating_operator();
         match self {
-            Self::Eq { in1: Some(in1), in2: Some(in2), .. } => {
-                let in1 = in1.to_math_expression()?;
-                let in2 = in2.to_math_expression()?;
+            Self::HBool {
+                operator,
+                out_type,
+                in1,
+                in2,
+                ..
+            } => truncates(operator, out_type)
+                .or_else(|| child(in1))
+                .or_else(|| child(in2)),
+            Self::TUnary {
+                operator,
+                all_type,
+                in1,
+                ..
+            } => truncates(operator, all_type).or_else(|| child(in1)),
+            Self::IUnary { in1, .. } | Self::Cast { in1, .. } => child(in1),
+            Self::Forall { body, .. } => child(body),
+            Self::Eq { in1, in2, .. } | Self::Ne { in1, in2, .. } => {
+                child(in1).or_else(|| child(in2))
+            }
+            Self::Type(_) | Self::DefinedConst(_) | Self::Const(_) | Self::Var(_) => None,
+        }
+    }
 
-                let check_eq = ConditionEqual::new(in1, in2);
+    fn to_math_expression(&self) -> Result<Pattern<MathExpression>> {
+        // this is really dumb, but I don't want to deal with RecExpr Ids
+        Ok(format!("{self}").parse()?)
+    }
+}
+
+// a theorem split apart at its top-level Eq
+#[derive(Debug, Clone)]
+struct SplitTheorem {
+    // hypotheses, which become rewrite conditions
+    conditions: Vec<LMEIntermediateRep>,
+    // the declared type of each (non-hypothesis) bound variable
+    variable_types: Vec<(String, String)>,
+    // the type the equality is stated in
+    carrier: Option<String>,
+    lhs: LMEIntermediateRep,
+    rhs: LMEIntermediateRep,
+}
+
+impl SplitTheorem {
+    fn carrier_sort(&self) -> Result<Sort> {
+        self.carrier
+            .as_deref()
+            .and_then(carrier_sort)
+            .ok_or_else(|| Error::msg(format!("unsupported carrier type: {:?}", self.carrier)))
+    }
+
+    // the largest sort each variable of the theorem may be instantiated with: its declared type,
+    // generalized by any matching embeddings (falling back to the carrier type if undeclared)
+    fn variable_sorts(
+        &self,
+        vars: Vec<Var>,
+        embeddings: &[SortEmbedding],
+    ) -> Result<Vec<(Var, Sort)>> {
+        let carrier = self.carrier_sort()?;
+
+        Ok(vars
+            .into_iter()
+            .map(|var| {
+                let sort = self
+                    .variable_types
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| format!("?{name}") == format!("{var}"))
+                    .and_then(|(_, typ)| carrier_sort(typ))
+                    .unwrap_or(carrier);
+                (var, embed(sort, embeddings))
+            })
+            .collect())
+    }
+}
+
+// a hypothesis of a rule: a pair of patterns which must (or mustn't) be equal for it to fire
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleCondition {
+    Equal(String, String),
+    NotEqual(String, String),
+}
+
+impl RuleCondition {
+    fn to_condition(&self) -> Result<JuniperCondition> {
+        match self {
+            Self::Equal(in1, in2) => {
+                let check_eq = ConditionEqual::new(in1.parse()?, in2.parse()?);
 
                 Ok(Box::new(move |egraph, id, subst| {
                     check_eq.check(egraph, id, subst)
                 }))
-            },
-            Self::Ne {  in1: Some(in1), in2: Some(in2), .. } => {
-                let in1 = in1.to_math_expression()?;
-                let in2 = in2.to_math_expression()?;
-
-                let check_eq = ConditionEqual::new(in1, in2);
+            }
+            Self::NotEqual(in1, in2) => {
+                let check_eq = ConditionEqual::new(in1.parse()?, in2.parse()?);
 
                 // this is technically unsound (if, in the future, in1 = in2, just not when you're
                 // currently checking)
@@ -771,97 +1041,370 @@ impl LMEIntermediateRep {
                     !check_eq.check(egraph, id, subst)
                 }))
             }
-            _ => Err(Error::msg(format!("Self::as_condition could not successfully convert the condition {self} into a closure"))),
         }
     }
+}
 
-    // convert intermediate representations into MathExpression Patterns
-    fn to_math_expression(self) -> Result<Pattern<MathExpression>> {
-        // this is really dumb, but I don't want to deal with RecExpr Ids
-        Ok(format!("{self}").parse()?)
+// a converted rewrite rule, in a form which can be stored and later turned into an egg Rewrite
+// without going back through the transpiler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompiledRule {
+    pub name: String,
+    // the theorem the rule was converted from
+    pub theorem: Name,
+    pub searcher: String,
+    pub applier: String,
+    pub conditions: Vec<RuleCondition>,
+    // the largest sort each variable bound by the searcher may be instantiated with
+    pub sorts: Vec<(String, Sort)>,
+    // the largest sort the matched term itself may have
+    pub carrier: Sort,
+}
+
+impl CompiledRule {
+    pub fn to_rewrite(&self) -> Result<MathRewrite> {
+        let searcher: Pattern<MathExpression> = self.searcher.parse()?;
+        let applier: Pattern<MathExpression> = self.applier.parse()?;
+        let sorts = self
+            .sorts
+            .iter()
+            .map(|(var, sort)| Ok((var.parse().map_err(Error::msg)?, *sort)))
+            .collect::<Result<Vec<(Var, Sort)>>>()?;
+
+        build_rewrite(
+            self.name.clone(),
+            searcher,
+            applier,
+            &self.conditions,
+            self.carrier,
+            sorts,
+        )
     }
 }
 
+fn build_rewrite(
+    name: String,
+    searcher: Pattern<MathExpression>,
+    applier: Pattern<MathExpression>,
+    conditions: &[RuleCondition],
+    carrier: Sort,
+    sorts: Vec<(Var, Sort)>,
+) -> Result<MathRewrite> {
+    let applier = create_condition_applier(applier, conditions, sort_condition(carrier, sorts))?;
+    Rewrite::new(name, searcher, applier).map_err(Error::msg)
+}
+
+// only allow a rule to fire when the matched term and every variable it binds are within their
+// allowed sorts (the variables alone aren't enough, as in 0 - 3 with both sides natural)
+fn sort_condition(carrier: Sort, bounds: Vec<(Var, Sort)>) -> JuniperCondition {
+    Box::new(move |egraph, eclass, subst| {
+        egraph[eclass].data.sort <= carrier
+            && bounds
+                .iter()
+                .all(|(var, bound)| egraph[subst[*var]].data.sort <= *bound)
+    })
+}
+
 // this is truly closure hell lol
 fn create_condition_applier(
     applier: Pattern<MathExpression>,
-    conditions: Vec<LMEIntermediateRep>,
-) -> Result<
-    ConditionalApplier<
-        Box<dyn Fn(&mut EGraph<MathExpression, ConstantFold>, Id, &Subst) -> bool + Send + Sync>,
-        Pattern<MathExpression>,
-    >,
-> {
-    if !conditions.is_empty() { println!("creating cond app for {applier:?} with conditions {conditions:?}"); }
+    conditions: &[RuleCondition],
+    sort_check: JuniperCondition,
+) -> Result<ConditionalApplier<JuniperCondition, Pattern<MathExpression>>> {
     Ok(ConditionalApplier {
         condition: Box::new({
-            conditions.into_iter().try_rfold::<Box<
-                dyn Fn(&mut EGraph<MathExpression, ConstantFold>, Id, &Subst) -> bool + Send + Sync,
-            >, _, Result<_>>(
-                Box::new(|_, _, _| true),
-                |acc, condition| {
-                    let condition_function = condition.clone().as_condition()?;
+            conditions
+                .iter()
+                .try_rfold::<JuniperCondition, _, Result<_>>(sort_check, |acc, condition| {
+                    let condition_function = condition.to_condition()?;
                     Ok(Box::new(move |e, i, s| {
                         acc(e, i, s) && condition_function(e, i, s)
                     }))
-                },
-            )?
+                })?
         }),
         applier,
     })
 }
 
-// convert a list of named LeanExprs into egg MathExpression rewrite rules
+// a rule along with the rewrite built from it
+type BuiltRule = (CompiledRule, MathRewrite);
+
+// the rules for each direction of a theorem, each of which may fail independently
+type DirectionalRules = (
+    std::result::Result<BuiltRule, String>,
+    std::result::Result<BuiltRule, String>,
+);
+
+// convert a single named LeanExpr into its forward and backward rules, tagging any error which
+// prevents both directions with the stage it occurred in
+fn entry_to_rules(
+    name: Name,
+    expr: LeanExpr,
+    field: bool,
+    embeddings: &[SortEmbedding],
+) -> Result<DirectionalRules, (ConversionStage, Error)> {
+    let intermediate =
+        LMEIntermediateRep::from_lean(expr).map_err(|e| (ConversionStage::Parse, e))?;
+    let Some(split) = intermediate.split_at_top_eq(Vec::new(), Vec::new()) else {
+        return Err((
+            ConversionStage::Split,
+            Error::msg(format!("no top-level equality found for {name}")),
+        ));
+    };
+
+    let eq1_me = split
+        .lhs
+        .to_math_expression()
+        .map_err(|e| (ConversionStage::Pattern, e))?;
+    let eq2_me = split
+        .rhs
+        .to_math_expression()
+        .map_err(|e| (ConversionStage::Pattern, e))?;
+
+    if eq1_me.to_string() == eq2_me.to_string() {
+        return Err((
+            ConversionStage::Pattern,
+            TrivialTheoremError {
+                pattern: eq1_me.to_string(),
+            }
+            .into(),
+        ));
+    }
+
+    let carrier = split
+        .carrier_sort()
+        .map_err(|e| (ConversionStage::Split, e))?;
+    // a cast's argument may be of a smaller sort than the carrier, so each operator is checked
+    // at the type it's applied at
+    if let Some((operator, sort)) = split
+        .lhs
+        .truncating_operator()
+        .or_else(|| split.rhs.truncating_operator())
+    {
+        return Err((
+            ConversionStage::Split,
+            Error::msg(format!(
+                "{name} uses {operator} over {sort}, where it doesn't agree with the reals"
+            )),
+        ));
+    }
+
+    // only the embeddings declared for this theorem generalize it
+    let embeddings = embeddings
+        .iter()
+        .filter(|embedding| embedding.applies_to(field))
+        .copied()
+        .collect::<Vec<_>>();
+    let embeddings = embeddings.as_slice();
+    let carrier = embed(carrier, embeddings);
+
+    let forward_sorts = split
+        .variable_sorts(eq1_me.vars(), embeddings)
+        .map_err(|e| (ConversionStage::Split, e))?;
+    let backward_sorts = split
+        .variable_sorts(eq2_me.vars(), embeddings)
+        .map_err(|e| (ConversionStage::Split, e))?;
+
+    let conditions = split
+        .conditions
+        .iter()
+        .map(|condition| condition.clone().into_rule_condition())
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| (ConversionStage::Condition, e))?;
+
+    // the rewrite is built from the patterns themselves, and the stored form alongside it
+    let rule = |direction: &str,
+                searcher: &Pattern<MathExpression>,
+                applier: &Pattern<MathExpression>,
+                sorts: Vec<(Var, Sort)>| {
+        let rule = CompiledRule {
+            name: format!("{name}_{direction}"),
+            theorem: name.clone(),
+            searcher: searcher.to_string(),
+            applier: applier.to_string(),
+            conditions: conditions.clone(),
+            sorts: sorts
+                .iter()
+                .map(|(var, sort)| (var.to_string(), *sort))
+                .collect(),
+            carrier,
+        };
+        build_rewrite(
+            rule.name.clone(),
+            searcher.clone(),
+            applier.clone(),
+            &conditions,
+            carrier,
+            sorts,
+        )
+        .map(|rewrite| (rule, rewrite))
+        .map_err(|e| e.to_string())
+    };
+
+    Ok((
+        rule("forward", &eq1_me, &eq2_me, forward_sorts),
+        rule("backward", &eq2_me, &eq1_me, backward_sorts),
+    ))
+}
+
+// collect whichever directions succeeded, only erroring out if no possible interpretation of the
+// given theorem was correct
+fn directions_to_vec(rules: DirectionalRules) -> Result<Vec<BuiltRule>> {
+    match rules {
+        (Err(ef), Err(eb)) => Err(Error::msg(format!(
+            "forward and backward rewrite failed:\n\tforward: {ef}\n\tbackward: {eb}"
+        ))),
+        (Ok(f), Err(_)) => Ok(vec![f]),
+        (Err(_), Ok(b)) => Ok(vec![b]),
+        (Ok(f), Ok(b)) => Ok(vec![f, b]),
+    }
+}
+
+// convert a list of named LeanExprs into egg MathExpression rewrite rules, each of which only fires
+// on terms within its theorem's carrier type (or a declared embedding of it). Trivial theorems
+// (see TrivialTheoremError) produce no rules
 pub fn lean_to_rewrites(
     lean_exprs: Vec<JuniperJsonEntry>,
-) -> Result<Vec<Rewrite<MathExpression, ConstantFold>>> {
+    embeddings: &[SortEmbedding],
+) -> Result<Vec<MathRewrite>> {
     let mut result = Vec::new();
-    for JuniperJsonEntry { name, typ: expr } in lean_exprs {
-        let intermediate = LMEIntermediateRep::from_lean(expr)?;
-        if let Some((conditions, eq1, eq2)) = intermediate.split_at_top_eq(Vec::new()) {
-            let eq1_me = eq1.to_math_expression()?;
-            let eq2_me = eq2.to_math_expression()?;
-
-            let forward_rewrite = Rewrite::new(
-                name.clone() + "_forward",
-                eq1_me.clone(),
-                create_condition_applier(eq2_me.clone(), conditions.clone())?,
-            );
-            let backward_rewrite = Rewrite::new(
-                name + "_backward",
-                eq2_me,
-                create_condition_applier(eq1_me, conditions)?,
-            );
-
-            // we only want to error out if no possible interpretation of the given theorem was
-            // correct
-            match (forward_rewrite, backward_rewrite) {
-                (Err(ef), Err(eb)) => {
-                    return Err(Error::msg(format!(
-                        "forward and backward rewrite failed:\n\tforward: {ef}\n\tbackward: {eb}"
-                    )))
+    for JuniperJsonEntry {
+        name,
+        typ: expr,
+        field,
+    } in lean_exprs
+    {
+        let rules = match entry_to_rules(name.clone(), expr, field, embeddings) {
+            Err((_, e)) if e.is::<TrivialTheoremError>() => continue,
+            rules => rules,
+        };
+        let rules = rules
+            .map_err(|(stage, e)| e.context(format!("{stage} stage failed")))
+            .and_then(directions_to_vec)
+            .with_context(|| format!("failed to convert theorem {name}"))?;
+        result.extend(rules.into_iter().map(|(_, rewrite)| rewrite));
+    }
+
+    Ok(result)
+}
+
+// like lean_to_rewrites, but skips theorems mentioning unknown constants instead of failing,
+// returning the skipped theorem names alongside the constant which caused each skip
+pub fn lean_to_rewrites_skip_unknown(
+    lean_exprs: Vec<JuniperJsonEntry>,
+    embeddings: &[SortEmbedding],
+) -> Result<(Vec<MathRewrite>, Vec<SkippedTheorem>)> {
+    let mut result = Vec::new();
+    let mut skipped = Vec::new();
+    for JuniperJsonEntry {
+        name,
+        typ: expr,
+        field,
+    } in lean_exprs
+    {
+        match entry_to_rules(name.clone(), expr, field, embeddings) {
+            Ok(rules) => result.extend(
+                directions_to_vec(rules)
+                    .with_context(|| format!("failed to convert theorem {name}"))?
+                    .into_iter()
+                    .map(|(_, rewrite)| rewrite),
+            ),
+            Err((_, e)) if e.is::<TrivialTheoremError>() => {}
+            Err((_, e)) => match e.downcast_ref::<UnknownConstantError>() {
+                Some(unknown) => skipped.push((name, unknown.clone())),
+                None => return Err(e.context(format!("failed to convert theorem {name}"))),
+            },
+        }
+    }
+
+    Ok((result, skipped))
+}
+
+fn lean_to_rules_lenient(
+    lean_exprs: Vec<JuniperJsonEntry>,
+    embeddings: &[SortEmbedding],
+) -> (Vec<BuiltRule>, ConversionReport) {
+    let mut result = Vec::new();
+    let mut report = ConversionReport::default();
+    for JuniperJsonEntry {
+        name,
+        typ: expr,
+        field,
+    } in lean_exprs
+    {
+        let mut theorem = TheoremReport {
+            name: name.clone(),
+            forward: false,
+            backward: false,
+            failures: Vec::new(),
+            skipped: None,
+        };
+
+        match entry_to_rules(name, expr, field, embeddings) {
+            Ok((forward, backward)) => {
+                match forward {
+                    Ok(f) => {
+                        theorem.forward = true;
+                        result.push(f);
+                    }
+                    Err(message) => theorem.failures.push(ConversionFailure {
+                        stage: ConversionStage::Rewrite,
+                        message: format!("forward: {message}"),
+                        subterm: None,
+                    }),
                 }
-                (Ok(f), Err(_)) => result.push(f),
-                (Err(_), Ok(b)) => result.push(b),
-                (Ok(f), Ok(b)) => {
-                    result.push(f);
-                    result.push(b);
+                match backward {
+                    Ok(b) => {
+                        theorem.backward = true;
+                        result.push(b);
+                    }
+                    Err(message) => theorem.failures.push(ConversionFailure {
+                        stage: ConversionStage::Rewrite,
+                        message: format!("backward: {message}"),
+                        subterm: None,
+                    }),
                 }
-            };
-        } else {
-            return Err(Error::msg(format!(
-                "error in some rewrite creation for {name}"
-            )));
+            }
+            Err((_, e)) if e.is::<TrivialTheoremError>() => theorem.skipped = Some(e.to_string()),
+            Err((stage, e)) => theorem.failures.push(ConversionFailure {
+                stage,
+                message: format!("{}", e.root_cause()),
+                subterm: e.downcast_ref::<OffendingSubterm>().map(|s| s.0.clone()),
+            }),
         }
+
+        report.theorems.push(theorem);
     }
 
-    Ok(result)
+    (result, report)
+}
+
+// convert every theorem that can be converted, never failing, and report on how each one went
+pub fn lean_to_rewrites_lenient(
+    lean_exprs: Vec<JuniperJsonEntry>,
+    embeddings: &[SortEmbedding],
+) -> (Vec<MathRewrite>, ConversionReport) {
+    let (rules, report) = lean_to_rules_lenient(lean_exprs, embeddings);
+    (
+        rules.into_iter().map(|(_, rewrite)| rewrite).collect(),
+        report,
+    )
+}
+
+// like lean_to_rewrites_lenient, but returns the rules in their storable form
+pub fn lean_to_compiled_rules_lenient(
+    lean_exprs: Vec<JuniperJsonEntry>,
+    embeddings: &[SortEmbedding],
+) -> (Vec<CompiledRule>, ConversionReport) {
+    let (rules, report) = lean_to_rules_lenient(lean_exprs, embeddings);
+    (rules.into_iter().map(|(rule, _)| rule).collect(), report)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::Result;
+    use lean_parse::lean_expr::BinderInfo;
 
     #[test]
     fn test_from_lean() -> Result<()> {
@@ -871,9 +1414,7 @@ mod tests {
         let add_zero_ir = LMEIntermediateRep::from_lean(add_zero_lean)?;
         let add_zero_manual = LMEIntermediateRep::Forall {
             binder_name: Some("a".to_string()),
-            binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                LMEIntermediateDefinedConst::Pi,
-            ))),
+            binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
             body: Some(Box::new(LMEIntermediateRep::Eq {
                 all_type: Some("Rat".to_string()),
                 in1: Some(Box::new(LMEIntermediateRep::HBool {
@@ -958,9 +1499,7 @@ mod tests {
         let inv_neg_ir = LMEIntermediateRep::from_lean(inv_neg_lean)?;
         let inv_neg_manual = LMEIntermediateRep::Forall {
             binder_name: Some("q".to_string()),
-            binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                LMEIntermediateDefinedConst::Pi,
-            ))),
+            binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
             body: Some(Box::new(LMEIntermediateRep::Eq {
                 all_type: Some("Rat".to_string()),
                 in1: Some(Box::new(LMEIntermediateRep::TUnary {
@@ -996,14 +1535,10 @@ mod tests {
         let mul_comm_ir = LMEIntermediateRep::from_lean(mul_comm_lean)?;
         let mul_comm_manual = LMEIntermediateRep::Forall {
             binder_name: Some("a".to_string()),
-            binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                LMEIntermediateDefinedConst::Pi,
-            ))),
+            binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
             body: Some(Box::new(LMEIntermediateRep::Forall {
                 binder_name: Some("b".to_string()),
-                binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                    LMEIntermediateDefinedConst::Pi,
-                ))),
+                binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
                 body: Some(Box::new(LMEIntermediateRep::Eq {
                     all_type: Some("Rat".to_string()),
                     in1: Some(Box::new(LMEIntermediateRep::HBool {
@@ -1036,9 +1571,7 @@ mod tests {
         let mul_inv_cancel_ir = LMEIntermediateRep::from_lean(mul_inv_cancel_lean)?;
         let mul_inv_cancel_manual = LMEIntermediateRep::Forall {
             binder_name: Some("a".to_string()),
-            binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                LMEIntermediateDefinedConst::Pi,
-            ))),
+            binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
             body: Some(Box::new(LMEIntermediateRep::Forall {
                 binder_name: Some("a._@.Mathlib.Data.Rat.Defs._hyg.3473".to_string()),
                 binder_type: Some(Box::new(LMEIntermediateRep::Ne {
@@ -1087,9 +1620,7 @@ mod tests {
         let neg_add_cancel_ir = LMEIntermediateRep::from_lean(neg_add_cancel_lean)?;
         let neg_add_cancel_manual = LMEIntermediateRep::Forall {
             binder_name: Some("a".to_string()),
-            binder_type: Some(Box::new(LMEIntermediateRep::DefinedConst(
-                LMEIntermediateDefinedConst::Pi,
-            ))),
+            binder_type: Some(Box::new(LMEIntermediateRep::Type("Rat".to_string()))),
             body: Some(Box::new(LMEIntermediateRep::Eq {
                 all_type: Some("Rat".to_string()),
                 in1: Some(Box::new(LMEIntermediateRep::HBool {
@@ -1180,4 +1711,415 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_unknown_constant() -> Result<()> {
+        let exp_zero_lean =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/exp_zero.json"))
+                .unwrap();
+        let exp_zero_err = LMEIntermediateRep::from_lean(exp_zero_lean.clone()).unwrap_err();
+
+        assert_eq!(
+            exp_zero_err.downcast_ref::<UnknownConstantError>(),
+            Some(&UnknownConstantError {
+                constant: "Real.exp".into()
+            })
+        );
+
+        let add_zero_lean =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero.json"))
+                .unwrap();
+        let entries = vec![
+            JuniperJsonEntry {
+                name: "Real.exp_zero".into(),
+                typ: exp_zero_lean,
+                field: false,
+            },
+            JuniperJsonEntry {
+                name: "Rat.add_zero".into(),
+                typ: add_zero_lean,
+                field: false,
+            },
+        ];
+
+        let strict_err = lean_to_rewrites(entries.clone(), &[]).unwrap_err();
+
+        assert!(format!("{strict_err}").contains("Real.exp_zero"));
+        assert!(strict_err.downcast_ref::<UnknownConstantError>().is_some());
+
+        let (rewrites, skipped) = lean_to_rewrites_skip_unknown(entries, &[])?;
+
+        assert_eq!(rewrites.len(), 2);
+        assert_eq!(
+            skipped,
+            vec![(
+                "Real.exp_zero".into(),
+                UnknownConstantError {
+                    constant: "Real.exp".into()
+                }
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_report() -> Result<()> {
+        let entries = vec![
+            JuniperJsonEntry {
+                name: "Real.exp_zero".into(),
+                typ: serde_json::from_str(include_str!("../../test_assets/exp_zero.json"))?,
+                field: false,
+            },
+            JuniperJsonEntry {
+                name: "Rat.add_zero".into(),
+                typ: serde_json::from_str(include_str!("../../test_assets/add_zero.json"))?,
+                field: false,
+            },
+            JuniperJsonEntry {
+                name: "Rat.mul_inv_cancel".into(),
+                typ: serde_json::from_str(include_str!("../../test_assets/mul_inv_cancel.json"))?,
+                field: false,
+            },
+        ];
+
+        let (rewrites, report) = lean_to_rewrites_lenient(entries, &[]);
+
+        assert_eq!(rewrites.len(), 3);
+        assert_eq!(report.converted().count(), 2);
+
+        let exp_zero = &report.theorems[0];
+
+        assert!(!exp_zero.is_converted());
+        assert_eq!(exp_zero.failures.len(), 1);
+        assert_eq!(exp_zero.failures[0].stage, ConversionStage::Parse);
+        assert_eq!(exp_zero.failures[0].message, "unknown constant: Real.exp");
+        assert!(matches!(
+            &exp_zero.failures[0].subterm,
+            Some(LeanExpr::Const { decl_name, .. }) if decl_name == "Real.exp"
+        ));
+
+        let add_zero = &report.theorems[1];
+
+        assert!(add_zero.forward && add_zero.backward);
+        assert!(add_zero.failures.is_empty());
+
+        // the right hand side `1` doesn't bind `a`, so only the forward direction is usable
+        let mul_inv_cancel = &report.theorems[2];
+
+        assert!(mul_inv_cancel.forward && !mul_inv_cancel.backward);
+        assert_eq!(mul_inv_cancel.failures.len(), 1);
+        assert_eq!(mul_inv_cancel.failures[0].stage, ConversionStage::Rewrite);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compiled_rules() -> Result<()> {
+        use egg::{AstSize, Extractor, Runner};
+
+        let entries = vec![JuniperJsonEntry {
+            name: "Rat.mul_inv_cancel".into(),
+            typ: serde_json::from_str(include_str!("../../test_assets/mul_inv_cancel.json"))?,
+            field: false,
+        }];
+
+        let (rules, report) = lean_to_compiled_rules_lenient(
+            entries,
+            &[SortEmbedding {
+                from: Sort::Rat,
+                to: Sort::Real,
+                field_only: false,
+            }],
+        );
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(report.converted().count(), 1);
+        assert_eq!(rules[0].name, "Rat.mul_inv_cancel_forward");
+        assert_eq!(rules[0].theorem, "Rat.mul_inv_cancel");
+        assert_eq!(rules[0].searcher, "(* ?a (inv ?a))");
+        assert_eq!(rules[0].applier, "1");
+        assert_eq!(rules[0].sorts, vec![("?a".to_string(), Sort::Real)]);
+        assert_eq!(
+            rules[0].conditions,
+            vec![RuleCondition::NotEqual("?a".to_string(), "0".to_string())]
+        );
+
+        // the stored form survives serialization, and still produces a working (conditional) rule
+        let stored: Vec<CompiledRule> = serde_json::from_str(&serde_json::to_string(&rules)?)?;
+        assert_eq!(stored, rules);
+
+        let rewrites = vec![stored[0].to_rewrite()?];
+        let simplify = |expr: &str| -> Result<String> {
+            let runner = Runner::<MathExpression, ConstantFold>::default()
+                .with_expr(&expr.parse()?)
+                .run(&rewrites);
+            let extractor = Extractor::new(&runner.egraph, AstSize);
+            Ok(extractor.find_best(runner.roots[0]).1.to_string())
+        };
+
+        assert_eq!(simplify("(* x (inv x))")?, "1");
+        assert_eq!(simplify("(* 0 (inv 0))")?, "(* 0 (inv 0))");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_restriction() -> Result<()> {
+        use egg::{AstSize, Extractor, Runner};
+
+        let entries = vec![JuniperJsonEntry {
+            name: "Rat.add_zero".into(),
+            typ: serde_json::from_str(include_str!("../../test_assets/add_zero.json"))?,
+            field: false,
+        }];
+
+        let simplify = |rules: &[MathRewrite], expr: &str| -> Result<String> {
+            let runner = Runner::<MathExpression, ConstantFold>::default()
+                .with_expr(&expr.parse()?)
+                .run(rules);
+            let extractor = Extractor::new(&runner.egraph, AstSize);
+            Ok(extractor.find_best(runner.roots[0]).1.to_string())
+        };
+
+        let rat_only = lean_to_rewrites(entries.clone(), &[])?;
+
+        // (inv 3) is rational, but (sin x) is real, so only the former is in Rat.add_zero's domain
+        assert_eq!(simplify(&rat_only, "(+ (inv 3) 0)")?, "(inv 3)");
+        assert_eq!(simplify(&rat_only, "(+ (sin x) 0)")?, "(+ (sin x) 0)");
+
+        let rat_to_real = lean_to_rewrites(
+            entries.clone(),
+            &[SortEmbedding {
+                from: Sort::Rat,
+                to: Sort::Real,
+                field_only: false,
+            }],
+        )?;
+
+        assert_eq!(simplify(&rat_to_real, "(+ (sin x) 0)")?, "(sin x)");
+
+        // an embedding for field identities doesn't generalize a theorem which isn't marked one
+        let others = lean_to_rewrites(
+            entries.clone(),
+            &[SortEmbedding {
+                from: Sort::Rat,
+                to: Sort::Real,
+                field_only: true,
+            }],
+        )?;
+
+        assert_eq!(simplify(&others, "(+ (sin x) 0)")?, "(+ (sin x) 0)");
+
+        let marked = entries
+            .iter()
+            .cloned()
+            .map(|entry| JuniperJsonEntry {
+                field: true,
+                ..entry
+            })
+            .collect();
+        let marked = lean_to_rewrites(
+            marked,
+            &[SortEmbedding {
+                from: Sort::Rat,
+                to: Sort::Real,
+                field_only: true,
+            }],
+        )?;
+
+        assert_eq!(simplify(&marked, "(+ (sin x) 0)")?, "(sin x)");
+
+        // the matched term has to be within the carrier too, not just the variables: 3 is natural
+        // but (inv 3) isn't
+        let rule = |carrier| CompiledRule {
+            name: "nat_inv".to_string(),
+            theorem: "nat_inv".into(),
+            searcher: "(inv ?a)".to_string(),
+            applier: "0".to_string(),
+            conditions: Vec::new(),
+            sorts: vec![("?a".to_string(), Sort::Nat)],
+            carrier,
+        };
+
+        assert_eq!(
+            simplify(&[rule(Sort::Nat).to_rewrite()?], "(inv 3)")?,
+            "(inv 3)"
+        );
+        assert_eq!(simplify(&[rule(Sort::Real).to_rewrite()?], "(inv 3)")?, "0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncating_operators() -> Result<()> {
+        // a - 0 = a over ℕ, where subtraction truncates
+        let nat_sub_zero = include_str!("../../test_assets/add_zero.json")
+            .replace("\"Rat\"", "\"Nat\"")
+            .replace("HAdd.hAdd", "HSub.hSub")
+            .replace("instHAdd", "instHSub")
+            .replace("Rat.instAdd", "instSubNat")
+            .replace("Rat.instOfNat", "instOfNatNat");
+        let entries = vec![JuniperJsonEntry {
+            name: "Nat.sub_zero".into(),
+            typ: serde_json::from_str(&nat_sub_zero)?,
+            field: false,
+        }];
+
+        let (rules, report) = lean_to_rewrites_lenient(entries, &[]);
+
+        assert!(rules.is_empty());
+        assert_eq!(report.theorems[0].failures[0].stage, ConversionStage::Split);
+        assert!(report.theorems[0].failures[0].message.contains("ℕ"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_casts() -> Result<()> {
+        use egg::{AstSize, Extractor, Runner};
+
+        let rpow_nat_cast_lean =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/rpow_natCast.json"))
+                .unwrap();
+        let rpow_nat_cast_ir = LMEIntermediateRep::from_lean(rpow_nat_cast_lean.clone())?;
+
+        // x ^ (↑n : ℝ) = x ^ n, where the cast is kept so that n has to be natural
+        let (rules, report) = lean_to_compiled_rules_lenient(
+            vec![JuniperJsonEntry {
+                name: "Real.rpow_natCast".into(),
+                typ: rpow_nat_cast_lean.clone(),
+                field: false,
+            }],
+            &[],
+        );
+
+        assert_eq!(report.converted().count(), 1);
+        assert_eq!(rules[0].searcher, "(^ ?x (cast ?n))");
+        assert_eq!(rules[0].applier, "(^ ?x ?n)");
+
+        let rules = rules
+            .iter()
+            .map(CompiledRule::to_rewrite)
+            .collect::<Result<Vec<_>>>()?;
+        let simplify = |expr: &str| -> Result<String> {
+            let runner = Runner::<MathExpression, ConstantFold>::default()
+                .with_expr(&expr.parse()?)
+                .run(&rules);
+            let extractor = Extractor::new(&runner.egraph, AstSize);
+            Ok(extractor.find_best(runner.roots[0]).1.to_string())
+        };
+
+        assert_eq!(simplify("(^ y (cast 3))")?, "(^ y 3)");
+        // a real (or rational) exponent isn't a cast natural
+        assert_eq!(simplify("(^ y (cast z))")?, "(^ y (cast z))");
+        assert_eq!(simplify("(^ y (cast 1/2))")?, "(^ y (cast 1/2))");
+        // and the backward rule doesn't cast its own casts again
+        assert_eq!(simplify("(^ y 3)")?, "(^ y 3)");
+
+        // a theorem whose sides are the same (here a = a) would only rewrite terms to themselves
+        let mut a_eq_a =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero.json"))?;
+        let LeanExpr::ForallE { body, .. } = &mut a_eq_a else {
+            panic!("add_zero is a ∀");
+        };
+        let LeanExpr::App { function, arg: rhs } = body.as_mut() else {
+            panic!("add_zero's body is an Eq");
+        };
+        let LeanExpr::App { arg: lhs, .. } = function.as_mut() else {
+            panic!("add_zero's body is an Eq");
+        };
+        *lhs = rhs.clone();
+        let (rules, report) = lean_to_rewrites_lenient(
+            vec![JuniperJsonEntry {
+                name: "a_eq_a".into(),
+                typ: a_eq_a,
+                field: false,
+            }],
+            &[],
+        );
+
+        assert!(rules.is_empty());
+        assert_eq!(report.skipped().count(), 1);
+        assert_eq!(report.failed().count(), 0);
+        assert_eq!(
+            report.theorems[0].to_string(),
+            "a_eq_a: skipped (both sides are ?a)"
+        );
+
+        let split = rpow_nat_cast_ir
+            .split_at_top_eq(Vec::new(), Vec::new())
+            .expect("rpow_natCast has a top-level Eq");
+
+        assert_eq!(
+            split.variable_types,
+            vec![
+                ("x".to_string(), "Real".to_string()),
+                ("n".to_string(), "Nat".to_string())
+            ]
+        );
+
+        let vars = split.lhs.to_math_expression()?.vars();
+        let sorts = split.variable_sorts(vars, &[])?;
+
+        assert_eq!(
+            sorts,
+            vec![
+                ("?x".parse().unwrap(), Sort::Real),
+                ("?n".parse().unwrap(), Sort::Nat)
+            ]
+        );
+
+        // a cast into a smaller sort isn't an embedding
+        let bad_cast = LMEIntermediateRep::app_state_next(
+            LMEIntermediateRep::Cast {
+                in_type: Some("Real".to_string()),
+                out_type: None,
+                inst: None,
+                in1: None,
+            },
+            LeanExpr::Const {
+                decl_name: "Nat".into(),
+                us: Vec::new(),
+            },
+        );
+
+        assert!(bad_cast.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize() -> Result<()> {
+        // each of these is add_zero with its lhs obscured by elaboration artifacts:
+        // `(let z : ℚ := 0; a + z)`, `(fun x : ℚ => x + 0) a` (under `noImplicitLambda` metadata)
+        // and `(instHAdd : HAdd ℚ ℚ ℚ).1 a 0`, as stated in lean/JuniperLean/Fixtures.lean
+        let assets = [
+            include_str!("../../test_assets/add_zero_let.json"),
+            include_str!("../../test_assets/add_zero_beta.json"),
+            include_str!("../../test_assets/add_zero_proj.json"),
+        ];
+
+        for asset in assets {
+            let lean = serde_json::from_str::<LeanExpr>(asset).unwrap();
+            let ir = LMEIntermediateRep::from_lean(lean)?;
+
+            assert_eq!(format!("{ir}"), "(= (+ ?a 0) ?a)".to_string());
+        }
+
+        // a lambda that isn't applied still can't be converted
+        let lambda = LeanExpr::Lam {
+            binder_name: "x".into(),
+            binder_type: Box::new(LeanExpr::Const {
+                decl_name: "Rat".into(),
+                us: Vec::new(),
+            }),
+            body: Box::new(LeanExpr::BVar { de_bruijn_index: 0 }),
+            binder_info: BinderInfo::Default,
+        };
+
+        assert!(LMEIntermediateRep::from_lean(lambda).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,100 @@
+use lean_parse::lean_expr::{LeanExpr, Name};
+
+fn constant(name: &str) -> LeanExpr {
+    LeanExpr::Const {
+        decl_name: name.into(),
+        us: Vec::new(),
+    }
+}
+
+// the projection function of the given structure field, for the classes the transpiler knows
+fn projection_function(type_name: &str, idx: u64) -> Option<&'static str> {
+    match (type_name, idx) {
+        ("HAdd", 0) => Some("HAdd.hAdd"),
+        ("HSub", 0) => Some("HSub.hSub"),
+        ("HMul", 0) => Some("HMul.hMul"),
+        ("HDiv", 0) => Some("HDiv.hDiv"),
+        ("HPow", 0) => Some("HPow.hPow"),
+        ("Neg", 0) => Some("Neg.neg"),
+        ("Inv", 0) => Some("Inv.inv"),
+        _ => None,
+    }
+}
+
+// the type arguments a projection function expects before the instance itself, recovered from a
+// known instance
+fn instance_type_args(instance: &LeanExpr) -> Option<Vec<LeanExpr>> {
+    let LeanExpr::Const { decl_name, .. } = instance.get_app_fn() else {
+        return None;
+    };
+
+    match (
+        decl_name.to_string().as_str(),
+        instance.get_app_args().as_slice(),
+    ) {
+        ("instHAdd" | "instHSub" | "instHMul" | "instHDiv", [alpha, _]) => {
+            Some(vec![(*alpha).clone(), (*alpha).clone(), (*alpha).clone()])
+        }
+        ("instHPow", [alpha, beta, _]) => {
+            Some(vec![(*alpha).clone(), (*beta).clone(), (*alpha).clone()])
+        }
+        ("Int.instNegInt", []) => Some(vec![constant("Int")]),
+        ("Rat.instNeg", []) => Some(vec![constant("Rat")]),
+        ("Rat.instInv", []) => Some(vec![constant("Rat")]),
+        ("Real.instNeg", []) => Some(vec![constant("Real")]),
+        ("Real.instInv", []) => Some(vec![constant("Real")]),
+        _ => None,
+    }
+}
+
+// rewrite a projection out of a known instance into an application of the corresponding
+// projection function (e.g. `(instHAdd ℝ inst).1` into `@HAdd.hAdd ℝ ℝ ℝ (instHAdd ℝ inst)`)
+fn resolve_projection(type_name: &Name, idx: u64, structure: LeanExpr) -> Option<LeanExpr> {
+    let function = projection_function(&type_name.to_string(), idx)?;
+    let type_args = instance_type_args(&structure)?;
+
+    Some(LeanExpr::mk_app(
+        constant(function),
+        type_args.into_iter().chain(std::iter::once(structure)),
+    ))
+}
+
+// bring an elaborated LeanExpr into the shape the transpiler expects: metadata is stripped, lets
+// are zeta-reduced, applied lambdas are beta-reduced and projections out of known instances are
+// replaced by their projection functions
+pub(crate) fn normalize(expr: LeanExpr) -> LeanExpr {
+    match expr {
+        LeanExpr::MData { expr, .. } => normalize(*expr),
+        LeanExpr::LetE { value, body, .. } => normalize(body.instantiate1(&value)),
+        LeanExpr::App { function, arg } => {
+            let function = normalize(*function);
+            let arg = normalize(*arg);
+            match function {
+                LeanExpr::Lam { body, .. } => normalize(body.instantiate1(&arg)),
+                function => LeanExpr::App {
+                    function: Box::new(function),
+                    arg: Box::new(arg),
+                },
+            }
+        }
+        e @ (LeanExpr::Lam { .. } | LeanExpr::ForallE { .. }) => {
+            e.map_children(|child, _| normalize(child.clone()))
+        }
+        LeanExpr::Proj {
+            type_name,
+            idx,
+            structure,
+        } => {
+            let structure = normalize(*structure);
+            match resolve_projection(&type_name, idx, structure.clone()) {
+                Some(resolved) => resolved,
+                None => LeanExpr::Proj {
+                    type_name,
+                    idx,
+                    structure: Box::new(structure),
+                },
+            }
+        }
+        e => e,
+    }
+}
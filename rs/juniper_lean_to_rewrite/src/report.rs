@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use lean_parse::lean_expr::{LeanExpr, Name};
+use serde::{Deserialize, Serialize};
+
+// the step of the LeanExpr to rewrite pipeline at which a theorem failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionStage {
+    // LeanExpr to intermediate representation
+    Parse,
+    // finding the top-level equality (and its hypotheses)
+    Split,
+    // intermediate representation to MathExpression patterns
+    Pattern,
+    // hypotheses to rewrite conditions
+    Condition,
+    // building the egg rewrites themselves
+    Rewrite,
+}
+
+impl Display for ConversionStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Parse => "parse",
+                Self::Split => "split",
+                Self::Pattern => "pattern",
+                Self::Condition => "condition",
+                Self::Rewrite => "rewrite",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionFailure {
+    pub stage: ConversionStage,
+    pub message: String,
+    pub subterm: Option<LeanExpr>,
+}
+
+impl Display for ConversionFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} stage failed: {}", self.stage, self.message)?;
+        if let Some(subterm) = &self.subterm {
+            write!(f, " (at `{}`)", subterm.pretty())?;
+        }
+        Ok(())
+    }
+}
+
+// the outcome of converting a single theorem, including which rewrite directions were created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TheoremReport {
+    pub name: Name,
+    pub forward: bool,
+    pub backward: bool,
+    pub failures: Vec<ConversionFailure>,
+    // why the theorem was deliberately left out, if it was (it would rewrite nothing)
+    #[serde(default)]
+    pub skipped: Option<String>,
+}
+
+impl TheoremReport {
+    // whether at least one rewrite direction was created for this theorem
+    pub fn is_converted(&self) -> bool {
+        self.forward || self.backward
+    }
+}
+
+impl Display for TheoremReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.forward, self.backward) {
+            (true, true) => write!(f, "{}: forward, backward", self.name)?,
+            (true, false) => write!(f, "{}: forward", self.name)?,
+            (false, true) => write!(f, "{}: backward", self.name)?,
+            (false, false) => match &self.skipped {
+                Some(reason) => write!(f, "{}: skipped ({reason})", self.name)?,
+                None => write!(f, "{}: not converted", self.name)?,
+            },
+        }
+        for failure in &self.failures {
+            write!(f, "\n\t{failure}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversionReport {
+    pub theorems: Vec<TheoremReport>,
+}
+
+impl ConversionReport {
+    // theorems which produced at least one rewrite
+    pub fn converted(&self) -> impl Iterator<Item = &TheoremReport> {
+        self.theorems.iter().filter(|t| t.is_converted())
+    }
+
+    // theorems which produced no rewrites at all, without being skipped
+    pub fn failed(&self) -> impl Iterator<Item = &TheoremReport> {
+        self.theorems
+            .iter()
+            .filter(|t| !t.is_converted() && t.skipped.is_none())
+    }
+
+    // theorems which were deliberately left out
+    pub fn skipped(&self) -> impl Iterator<Item = &TheoremReport> {
+        self.theorems.iter().filter(|t| t.skipped.is_some())
+    }
+}
+
+impl Display for ConversionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "converted {} of {} theorems",
+            self.converted().count(),
+            self.theorems.len()
+        )?;
+        for theorem in &self.theorems {
+            write!(f, "\n{theorem}")?;
+        }
+        Ok(())
+    }
+}
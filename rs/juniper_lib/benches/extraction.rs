@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use egg::{rewrite, AstSize, Extractor, Id, Language, RecExpr};
+use juniper_lib::{DagExtractor, JuniperRewrite, MathExpression, Simplifier, WeightedCost};
+
+const SAMPLES: u32 = 20;
+
+fn rules() -> Vec<JuniperRewrite> {
+    [
+        rewrite!("square"; "(^ ?a 2)" <=> "(* ?a ?a)"),
+        rewrite!("sin_sq"; "(^ (sin ?a) 2)" <=> "(- 1 (^ (cos ?a) 2))"),
+        vec![
+            rewrite!("add_comm"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+            rewrite!("mul_comm"; "(* ?a ?b)" => "(* ?b ?a)"),
+            rewrite!("double"; "(+ ?a ?a)" => "(* 2 ?a)"),
+        ],
+    ]
+    .concat()
+}
+
+// a sum of n terms which all repeat the same few sines
+fn expression(n: usize) -> RecExpr<MathExpression> {
+    let term = |i: usize| format!("(* (^ (sin (+ x {})) 2) (sin y))", i % 3);
+    let sum = (1..n).fold(term(0), |sum, i| format!("(+ {sum} {})", term(i)));
+    sum.parse().unwrap()
+}
+
+// the number of distinct subexpressions, which is the DAG cost under AstSize
+fn dag_size(expr: &RecExpr<MathExpression>) -> usize {
+    let mut distinct = HashMap::new();
+    let mut ids = Vec::new();
+    for node in expr.as_ref() {
+        let node = node.clone().map_children(|child| ids[usize::from(child)]);
+        let next = Id::from(distinct.len());
+        ids.push(*distinct.entry(node).or_insert(next));
+    }
+    distinct.len()
+}
+
+fn time<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    for _ in 1..SAMPLES {
+        f();
+    }
+    let result = f();
+    (start.elapsed() / SAMPLES, result)
+}
+
+fn main() {
+    let simplifier = Simplifier::new(rules()).with_iter_limit(8);
+
+    println!("terms  e-nodes  tree (AstSize)          dag (AstSize)           dag (weighted)");
+    for n in [2, 4, 8, 16, 32] {
+        let runner = simplifier
+            .runner()
+            .with_expr(&expression(n))
+            .run(simplifier.rules());
+        let root = runner.roots[0];
+
+        let (tree_time, (_, tree)) =
+            time(|| Extractor::new(&runner.egraph, AstSize).find_best(root));
+        let (dag_time, (dag_cost, _)) =
+            time(|| DagExtractor::new(&runner.egraph, AstSize).find_best(root));
+        let (weighted_time, (weighted_cost, _)) =
+            time(|| DagExtractor::new(&runner.egraph, WeightedCost::default()).find_best(root));
+
+        println!(
+            "{n:>5}  {:>7}  {:>5} in {:>12?}  {:>5} in {:>12?}  {:>5} in {:>12?}",
+            runner.egraph.total_size(),
+            dag_size(&tree),
+            tree_time,
+            dag_cost,
+            dag_time,
+            weighted_cost,
+            weighted_time,
+        );
+    }
+}
@@ -1,13 +1,65 @@
-use std::{io::Write, process::Command};
+use std::collections::HashSet;
+use std::env;
+use std::io::Write;
+use std::process::Command;
 
-fn main() {
-    println!("cargo::rerun-if-changed=../../../lean");
+use juniper_lean_to_rewrite::JuniperJsonEntry;
+
+const EXPORT: &str = "../../exported.json";
+
+// re-elaborate the Lean project, which writes a fresh exported.json
+fn rebuild_lean() {
+    println!("cargo::rerun-if-changed=../../lean");
     let mut rebuild = Command::new("lake");
 
     rebuild.arg("lean").arg("JuniperLean.lean");
     rebuild.current_dir("../../lean/");
 
-    let output = rebuild.output().expect("Building JupiterLean failed");
+    let output = rebuild.output().unwrap_or_else(|error| {
+        panic!("couldn't run lake (the lean-rebuild feature needs a Lean toolchain): {error}")
+    });
 
     std::io::stdout().write_all(&output.stdout).unwrap();
+    if !output.status.success() {
+        panic!(
+            "building JuniperLean failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+// check the export parses, has distinct theorem names and that every theorem in it converts to at
+// least one rule, so a broken export fails the build rather than get_juniper_rules
+fn validate_export() {
+    let json = std::fs::read_to_string(EXPORT).unwrap_or_else(|error| {
+        panic!(
+            "couldn't read {EXPORT} ({error}); build with `--features lean-rebuild` to generate it"
+        )
+    });
+    let theorems: Vec<JuniperJsonEntry> = serde_json::from_str(&json)
+        .unwrap_or_else(|error| panic!("{EXPORT} isn't a valid theorem export: {error}"));
+
+    let mut names = HashSet::new();
+    if let Some(duplicate) = theorems.iter().find(|t| !names.insert(t.name())) {
+        panic!("{EXPORT} exports {} more than once", duplicate.name());
+    }
+
+    // sort embeddings only add conditions to rules, so they can't make a conversion fail
+    let (_, report) = juniper_lean_to_rewrite::lean_to_rewrites_lenient(theorems, &[]);
+    let failed = report.failed().map(|t| t.to_string()).collect::<Vec<_>>();
+    if !failed.is_empty() {
+        panic!(
+            "{} theorem(s) in {EXPORT} couldn't be converted:\n{}",
+            failed.len(),
+            failed.join("\n")
+        );
+    }
+}
+
+fn main() {
+    println!("cargo::rerun-if-changed={EXPORT}");
+    if env::var_os("CARGO_FEATURE_LEAN_REBUILD").is_some() {
+        rebuild_lean();
+    }
+    validate_export();
 }
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{Error, Result};
+use egg::{AstSize, CostFunction, Id, Language};
+
+use crate::MathExpression;
+
+// the kinds of MathExpression node, without their children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operator {
+    Constant,
+    Pi,
+    Variable,
+    Assign,
+    Eq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Sqrt,
+    Neg,
+    Inv,
+    Sin,
+    Cos,
+    Cast,
+    Antiderivative,
+    Derivative,
+    Integral,
+}
+
+const OPERATOR_NAMES: &[(Operator, &str)] = &[
+    (Operator::Constant, "constant"),
+    (Operator::Pi, "pi"),
+    (Operator::Variable, "variable"),
+    (Operator::Assign, "assign"),
+    (Operator::Eq, "eq"),
+    (Operator::Add, "add"),
+    (Operator::Sub, "sub"),
+    (Operator::Mul, "mul"),
+    (Operator::Div, "div"),
+    (Operator::Pow, "pow"),
+    (Operator::Sqrt, "sqrt"),
+    (Operator::Neg, "neg"),
+    (Operator::Inv, "inv"),
+    (Operator::Sin, "sin"),
+    (Operator::Cos, "cos"),
+    (Operator::Cast, "cast"),
+    (Operator::Antiderivative, "antiderivative"),
+    (Operator::Derivative, "derivative"),
+    (Operator::Integral, "integral"),
+];
+
+impl Operator {
+    pub fn of(node: &MathExpression) -> Operator {
+        match node {
+            MathExpression::Constant(_) => Operator::Constant,
+            MathExpression::Pi => Operator::Pi,
+            MathExpression::Variable(_) => Operator::Variable,
+            MathExpression::Assign(_) => Operator::Assign,
+            MathExpression::Eq(_) => Operator::Eq,
+            MathExpression::Add(_) => Operator::Add,
+            MathExpression::Sub(_) => Operator::Sub,
+            MathExpression::Mul(_) => Operator::Mul,
+            MathExpression::Div(_) => Operator::Div,
+            MathExpression::Pow(_) => Operator::Pow,
+            MathExpression::Sqrt(_) => Operator::Sqrt,
+            MathExpression::Neg(_) => Operator::Neg,
+            MathExpression::Inv(_) => Operator::Inv,
+            MathExpression::Sin(_) => Operator::Sin,
+            MathExpression::Cos(_) => Operator::Cos,
+            MathExpression::Cast(_) => Operator::Cast,
+            MathExpression::Antiderivative(_) => Operator::Antiderivative,
+            MathExpression::Derivative(_) => Operator::Derivative,
+            MathExpression::Integral(_) => Operator::Integral,
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = OPERATOR_NAMES
+            .iter()
+            .find(|(operator, _)| operator == self)
+            .expect("every operator has a name");
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Operator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OPERATOR_NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(operator, _)| *operator)
+            .ok_or_else(|| Error::msg(format!("unknown operator: {s}")))
+    }
+}
+
+// the number of decimal digits needed to write a constant, beyond the first
+fn extra_digits(node: &MathExpression) -> usize {
+    match node {
+        MathExpression::Constant(c) => {
+            let numer = c.0.numer().magnitude().to_string().len();
+            let denom = if c.0.is_integer() {
+                0
+            } else {
+                c.0.denom().to_string().len()
+            };
+            numer + denom - 1
+        }
+        _ => 0,
+    }
+}
+
+// the cost of a single node, not counting its children, for extraction which counts shared
+// subexpressions once (see DagExtractor)
+pub trait NodeCost {
+    fn node_cost(&self, node: &MathExpression) -> f64;
+}
+
+impl NodeCost for AstSize {
+    fn node_cost(&self, _node: &MathExpression) -> f64 {
+        1.0
+    }
+}
+
+fn check_weight(weight: f64) -> Result<f64> {
+    if weight.is_finite() && weight >= 0.0 {
+        Ok(weight)
+    } else {
+        Err(Error::msg(format!(
+            "weights must be finite and non-negative, not {weight}"
+        )))
+    }
+}
+
+// the sum of a weight for every node of an expression (1 unless configured otherwise), plus a
+// weight for every digit of its constants beyond the first
+#[derive(Debug, Clone)]
+pub struct WeightedCost {
+    weights: HashMap<Operator, f64>,
+    digit_weight: f64,
+}
+
+impl WeightedCost {
+    // every node costs 1, like AstSize
+    pub fn uniform() -> Self {
+        Self {
+            weights: HashMap::new(),
+            digit_weight: 0.0,
+        }
+    }
+
+    // weights have to be finite and non-negative: egg's extractor panics on NaN costs, and
+    // negative ones can make a cycle of e-classes ever cheaper
+    pub fn with_weight(mut self, operator: Operator, weight: f64) -> Result<Self> {
+        self.weights.insert(operator, check_weight(weight)?);
+        Ok(self)
+    }
+
+    pub fn with_digit_weight(mut self, digit_weight: f64) -> Result<Self> {
+        self.digit_weight = check_weight(digit_weight)?;
+        Ok(self)
+    }
+
+    pub fn weight(&self, operator: Operator) -> f64 {
+        self.weights.get(&operator).copied().unwrap_or(1.0)
+    }
+}
+
+impl Default for WeightedCost {
+    // inverses, roots and trig are penalized (in that order), as are big constants
+    fn default() -> Self {
+        Self {
+            weights: HashMap::from([
+                (Operator::Inv, 2.0),
+                (Operator::Div, 2.0),
+                (Operator::Sqrt, 3.0),
+                (Operator::Sin, 4.0),
+                (Operator::Cos, 4.0),
+            ]),
+            digit_weight: 0.5,
+        }
+    }
+}
+
+impl NodeCost for WeightedCost {
+    fn node_cost(&self, node: &MathExpression) -> f64 {
+        self.weight(Operator::of(node)) + self.digit_weight * extra_digits(node) as f64
+    }
+}
+
+impl CostFunction<MathExpression> for WeightedCost {
+    type Cost = f64;
+
+    fn cost<C>(&mut self, enode: &MathExpression, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        enode.fold(self.node_cost(enode), |sum, id| sum + costs(id))
+    }
+}
+
+// prefers expressions with fewer occurrences of variables, breaking ties by size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VariableOccurrences;
+
+impl CostFunction<MathExpression> for VariableOccurrences {
+    // (variable occurrences, AstSize)
+    type Cost = (usize, usize);
+
+    fn cost<C>(&mut self, enode: &MathExpression, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        let own = usize::from(matches!(enode, MathExpression::Variable(_)));
+        let size = AstSize.cost(enode, |id| costs(id).1);
+        enode.fold((own, size), |(occurrences, size), id| {
+            (occurrences + costs(id).0, size)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use egg::{CostFunction, RecExpr};
+
+    use super::{Operator, VariableOccurrences, WeightedCost};
+    use crate::MathExpression;
+
+    fn cost<C: CostFunction<MathExpression>>(mut cost_function: C, expr: &str) -> Result<C::Cost> {
+        Ok(cost_function.cost_rec(&expr.parse::<RecExpr<MathExpression>>()?))
+    }
+
+    #[test]
+    fn test_weighted_cost() -> Result<()> {
+        let weighted = WeightedCost::default();
+
+        // trig is penalized, so the power is cheaper than the product
+        assert_eq!(cost(weighted.clone(), "(^ (sin x) 2)")?, 7.0);
+        assert_eq!(cost(weighted.clone(), "(* (sin x) (sin x))")?, 11.0);
+        // as are extra digits
+        assert_eq!(cost(weighted.clone(), "1000")?, 2.5);
+        assert_eq!(cost(weighted.clone(), "-3/4")?, 1.5);
+
+        let custom = WeightedCost::uniform().with_weight("pow".parse()?, 10.0)?;
+        assert_eq!(cost(custom, "(^ x 2)")?, 12.0);
+
+        // NaN and negative weights would break extraction
+        assert!(WeightedCost::uniform()
+            .with_weight(Operator::Add, f64::NAN)
+            .is_err());
+        assert!(WeightedCost::uniform()
+            .with_weight(Operator::Add, -1.0)
+            .is_err());
+        assert!(WeightedCost::uniform()
+            .with_digit_weight(f64::INFINITY)
+            .is_err());
+        assert!(WeightedCost::uniform()
+            .with_weight(Operator::Add, 0.0)
+            .is_ok());
+
+        assert_eq!(Operator::Antiderivative.to_string(), "antiderivative");
+        assert!("tan".parse::<Operator>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable_occurrences() -> Result<()> {
+        assert_eq!(cost(VariableOccurrences, "(+ (* x y) x)")?, (3, 5));
+        assert_eq!(cost(VariableOccurrences, "(* x (+ y 1))")?, (2, 5));
+
+        Ok(())
+    }
+}
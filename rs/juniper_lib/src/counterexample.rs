@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+
+use egg::{Id, RecExpr};
+
+use crate::{approximate_with, MathExpression};
+
+// the outcome of comparing two expressions at sampled values of their variables
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumericCheck {
+    // the expressions differ at these values, so they can't be equal
+    Counterexample {
+        assignment: BTreeMap<char, f64>,
+        lhs: f64,
+        rhs: f64,
+    },
+    // the expressions agreed at every sample where both were defined. The confidence is the
+    // chance that the next sample agrees too (by Laplace's rule of succession), which says nothing
+    // about disagreements at isolated points
+    ProbablyEqual {
+        samples: usize,
+        confidence: f64,
+    },
+    // no sample could be evaluated, because an expression can't be approximated (such as one
+    // with derivatives) or was never defined at the sampled values
+    Undetermined,
+}
+
+impl Display for NumericCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumericCheck::Counterexample {
+                assignment,
+                lhs,
+                rhs,
+            } => {
+                let assignment = assignment
+                    .iter()
+                    .map(|(variable, value)| format!("{variable} = {value}"))
+                    .collect::<Vec<_>>();
+                write!(f, "counterexample: ")?;
+                if !assignment.is_empty() {
+                    write!(f, "at {}, ", assignment.join(", "))?;
+                }
+                write!(f, "{lhs} ≠ {rhs}")
+            }
+            NumericCheck::ProbablyEqual {
+                samples,
+                confidence,
+            } => write!(
+                f,
+                "probably equal (agreed at {samples} sample(s), confidence {confidence:.3})"
+            ),
+            NumericCheck::Undetermined => write!(f, "undetermined (no sample could be evaluated)"),
+        }
+    }
+}
+
+// splitmix64, which is plenty for picking sample points and keeps searches reproducible
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [-range, range)
+    fn next_f64(&mut self, range: f64) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        (unit * 2.0 - 1.0) * range
+    }
+}
+
+fn variables(expr: &RecExpr<MathExpression>) -> BTreeSet<char> {
+    expr.as_ref()
+        .iter()
+        .filter_map(|node| match node {
+            MathExpression::Variable(v) => Some(*v),
+            _ => None,
+        })
+        .collect()
+}
+
+// whether every subexpression is defined (as a real number) at the given values. approximate
+// follows Lean in taking the inverse of 0 to be 0, so that's ruled out here too
+fn defined(expr: &RecExpr<MathExpression>, variables: &HashMap<char, f64>) -> bool {
+    expr.as_ref().iter().enumerate().all(|(i, node)| {
+        let value = |id: &Id| approximate_with(expr, id, variables);
+        let nonzero = |id: &Id| value(id).is_some_and(|v| v != 0.0);
+        let finite = value(&Id::from(i)).is_some_and(f64::is_finite);
+        match node {
+            MathExpression::Inv(n) | MathExpression::Div([_, n]) => finite && nonzero(n),
+            _ => finite,
+        }
+    })
+}
+
+// a randomized search for values of the variables at which two expressions differ, which is
+// much cheaper than saturation and so makes a good pre-check for it
+#[derive(Debug, Clone)]
+pub struct CounterexampleSearch {
+    samples: usize,
+    range: f64,
+    tolerance: f64,
+    seed: u64,
+}
+
+impl Default for CounterexampleSearch {
+    fn default() -> Self {
+        Self {
+            samples: 100,
+            range: 10.0,
+            tolerance: 1e-9,
+            seed: 0,
+        }
+    }
+}
+
+impl CounterexampleSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    // variables are sampled uniformly from [-range, range)
+    pub fn with_range(mut self, range: f64) -> Self {
+        self.range = range;
+        self
+    }
+
+    // the relative difference two values may have while still counting as equal
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn agree(&self, lhs: f64, rhs: f64) -> bool {
+        (lhs - rhs).abs() <= self.tolerance * lhs.abs().max(rhs.abs()).max(1.0)
+    }
+
+    pub fn check(
+        &self,
+        lhs: &RecExpr<MathExpression>,
+        rhs: &RecExpr<MathExpression>,
+    ) -> NumericCheck {
+        let mut names = variables(lhs);
+        names.extend(variables(rhs));
+
+        let mut rng = SplitMix64(self.seed);
+        let mut agreed = 0;
+        // without variables there's only one point to sample
+        let samples = if names.is_empty() { 1 } else { self.samples };
+        for _ in 0..samples {
+            let assignment = names
+                .iter()
+                .map(|name| (*name, rng.next_f64(self.range)))
+                .collect::<HashMap<_, _>>();
+            if !defined(lhs, &assignment) || !defined(rhs, &assignment) {
+                continue;
+            }
+
+            // both are defined, so both can be approximated
+            let lhs = approximate_with(lhs, &lhs.root(), &assignment).unwrap();
+            let rhs = approximate_with(rhs, &rhs.root(), &assignment).unwrap();
+            if !self.agree(lhs, rhs) {
+                return NumericCheck::Counterexample {
+                    assignment: assignment.into_iter().collect(),
+                    lhs,
+                    rhs,
+                };
+            }
+            agreed += 1;
+        }
+
+        if agreed == 0 {
+            NumericCheck::Undetermined
+        } else {
+            NumericCheck::ProbablyEqual {
+                samples: agreed,
+                confidence: (agreed as f64 + 1.0) / (agreed as f64 + 2.0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{CounterexampleSearch, NumericCheck};
+
+    fn check(lhs: &str, rhs: &str) -> Result<NumericCheck> {
+        Ok(CounterexampleSearch::new().check(&lhs.parse()?, &rhs.parse()?))
+    }
+
+    #[test]
+    fn test_counterexample_search() -> Result<()> {
+        assert!(matches!(
+            check("(+ (^ (sin x) 2) (^ (cos x) 2))", "1")?,
+            NumericCheck::ProbablyEqual { samples: 100, .. }
+        ));
+
+        let NumericCheck::Counterexample {
+            assignment,
+            lhs,
+            rhs,
+        } = check("(^ (+ x y) 2)", "(+ (^ x 2) (^ y 2))")?
+        else {
+            panic!("(x + y)^2 isn't x^2 + y^2");
+        };
+        assert_eq!(assignment.keys().collect::<Vec<_>>(), [&'x', &'y']);
+        assert_ne!(lhs, rhs);
+
+        // sqrt is only sampled where it's defined, so this holds
+        assert!(matches!(
+            check("(^ (sqrt x) 2)", "x")?,
+            NumericCheck::ProbablyEqual { .. }
+        ));
+        // but not the other way around
+        assert!(matches!(
+            check("(sqrt (^ x 2))", "x")?,
+            NumericCheck::Counterexample { .. }
+        ));
+
+        assert!(matches!(
+            check("(+ 1 2)", "4")?,
+            NumericCheck::Counterexample { .. }
+        ));
+        assert_eq!(check("(d x x)", "1")?, NumericCheck::Undetermined);
+
+        Ok(())
+    }
+}
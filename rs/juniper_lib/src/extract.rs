@@ -0,0 +1,574 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::Error;
+use egg::{CostFunction, Extractor, Id, Language, RecExpr, StopReason};
+
+use crate::{JuniperEGraph, MathExpression, NodeCost, Operator};
+
+// the operators and variables an extracted expression may not contain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Forbidden {
+    operators: BTreeSet<Operator>,
+    variables: BTreeSet<char>,
+}
+
+impl Forbidden {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_operator(mut self, operator: Operator) -> Self {
+        self.operators.insert(operator);
+        self
+    }
+
+    pub fn with_variable(mut self, variable: char) -> Self {
+        self.variables.insert(variable);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty() && self.variables.is_empty()
+    }
+
+    pub fn forbids(&self, node: &MathExpression) -> bool {
+        match node {
+            MathExpression::Variable(v) => self.variables.contains(v),
+            _ => self.operators.contains(&Operator::of(node)),
+        }
+    }
+}
+
+impl Display for Forbidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self
+            .operators
+            .iter()
+            .map(Operator::to_string)
+            .chain(self.variables.iter().map(char::to_string))
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+impl FromStr for Forbidden {
+    type Err = Error;
+
+    // whitespace separated operator names (see Operator) and single letter variables
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace()
+            .try_fold(Self::new(), |forbidden, item| {
+                match (
+                    item.parse::<Operator>(),
+                    item.chars().collect::<Vec<_>>().as_slice(),
+                ) {
+                    (Ok(operator), _) => Ok(forbidden.with_operator(operator)),
+                    (Err(_), [variable]) if variable.is_alphabetic() => {
+                        Ok(forbidden.with_variable(*variable))
+                    }
+                    (Err(e), _) => Err(e),
+                }
+            })
+    }
+}
+
+// a cost which is either that of an allowed expression or marks a forbidden one (which is more
+// expensive than any allowed expression)
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Constrained<Cost> {
+    Allowed(Cost),
+    Forbidden,
+}
+
+// wraps a cost function so that expressions containing forbidden operators or variables are
+// only extracted when there's no alternative
+#[derive(Debug, Clone)]
+pub struct Forbidding<C> {
+    cost_function: C,
+    forbidden: Forbidden,
+}
+
+impl<C> Forbidding<C> {
+    pub fn new(cost_function: C, forbidden: Forbidden) -> Self {
+        Self {
+            cost_function,
+            forbidden,
+        }
+    }
+}
+
+impl<C: CostFunction<MathExpression>> CostFunction<MathExpression> for Forbidding<C> {
+    type Cost = Constrained<C::Cost>;
+
+    fn cost<F>(&mut self, enode: &MathExpression, mut costs: F) -> Self::Cost
+    where
+        F: FnMut(Id) -> Self::Cost,
+    {
+        if self.forbidden.forbids(enode)
+            || enode
+                .children()
+                .iter()
+                .any(|id| costs(*id) == Constrained::Forbidden)
+        {
+            return Constrained::Forbidden;
+        }
+
+        Constrained::Allowed(self.cost_function.cost(enode, |id| match costs(id) {
+            Constrained::Allowed(cost) => cost,
+            Constrained::Forbidden => unreachable!("forbidden children were ruled out"),
+        }))
+    }
+}
+
+// no expression equivalent to the input (in the saturated e-graph) avoids the forbidden operators
+// and variables
+#[derive(Debug, Clone)]
+pub struct NoRepresentativeError {
+    pub forbidden: Forbidden,
+    pub stop_reason: StopReason,
+}
+
+impl Display for NoRepresentativeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no equivalent expression avoids {} (saturation stopped: {:?})",
+            self.forbidden, self.stop_reason
+        )
+    }
+}
+
+impl std::error::Error for NoRepresentativeError {}
+
+// the representatives of an e-class, with their costs
+pub type Candidates<Cost> = Vec<(Cost, RecExpr<MathExpression>)>;
+
+// extracts up to k structurally distinct representatives of every e-class, cheapest first (by
+// tree cost, like egg's Extractor)
+pub struct KBestExtractor<'a, C: CostFunction<MathExpression>> {
+    egraph: &'a JuniperEGraph,
+    k: usize,
+    cost_function: C,
+    candidates: HashMap<Id, Candidates<C::Cost>>,
+}
+
+impl<'a, C: CostFunction<MathExpression>> KBestExtractor<'a, C> {
+    pub fn new(egraph: &'a JuniperEGraph, cost_function: C, k: usize) -> Self {
+        let mut extractor = Self {
+            egraph,
+            k,
+            cost_function,
+            candidates: HashMap::new(),
+        };
+        extractor.find_costs();
+        extractor
+    }
+
+    // the cheapest representatives of an e-class, without duplicates
+    pub fn find_k_best(&self, eclass: Id) -> Candidates<C::Cost> {
+        self.candidates
+            .get(&self.egraph.find(eclass))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // like egg's Extractor, repeatedly improves every class until nothing changes, except that
+    // each class keeps its k best trees rather than just the best one
+    fn find_costs(&mut self) {
+        if self.k == 0 {
+            return;
+        }
+
+        let egraph = self.egraph;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for class in egraph.classes() {
+                let mut candidates = self.candidates.get(&class.id).cloned().unwrap_or_default();
+                for node in &class.nodes {
+                    candidates.extend(self.node_candidates(node));
+                }
+
+                // existing candidates come first, so ties never displace them
+                candidates.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let mut distinct: Candidates<C::Cost> = Vec::new();
+                for (cost, expr) in candidates {
+                    if distinct.len() < self.k && distinct.iter().all(|(_, e)| *e != expr) {
+                        distinct.push((cost, expr));
+                    }
+                }
+
+                if self.candidates.get(&class.id) != Some(&distinct) {
+                    self.candidates.insert(class.id, distinct);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    // the k cheapest combinations of the children's current candidates under a node. Each child's
+    // candidates are sorted, so combinations are enumerated best first from all-cheapest, only
+    // ever trying the successors (one child's next candidate) of those already taken, rather than
+    // building all k^arity of them. This relies on a node's cost never decreasing as a child's
+    // does, which extraction already assumes
+    fn node_candidates(&mut self, node: &MathExpression) -> Candidates<C::Cost> {
+        let mut children = Vec::new();
+        for child in node.children() {
+            match self.candidates.get(&self.egraph.find(*child)) {
+                Some(candidates) if !candidates.is_empty() => children.push(candidates),
+                _ => return Vec::new(),
+            }
+        }
+
+        // the same class may be a child more than once, so children are referred to by position
+        let mut position = 0;
+        let node = node.clone().map_children(|_| {
+            position += 1;
+            Id::from(position - 1)
+        });
+        let mut cost_of = |combination: &[usize]| {
+            self.cost_function.cost(&node, |id| {
+                children[usize::from(id)][combination[usize::from(id)]]
+                    .0
+                    .clone()
+            })
+        };
+
+        let first = vec![0; children.len()];
+        let mut frontier = vec![(cost_of(&first), first.clone())];
+        let mut seen = HashSet::from([first]);
+        let mut result = Vec::new();
+        while result.len() < self.k {
+            let Some(cheapest) = (0..frontier.len()).min_by(|&a, &b| {
+                frontier[a]
+                    .0
+                    .partial_cmp(&frontier[b].0)
+                    .unwrap_or(Ordering::Equal)
+            }) else {
+                break;
+            };
+            let (cost, combination) = frontier.swap_remove(cheapest);
+
+            for i in 0..children.len() {
+                if combination[i] + 1 < children[i].len() {
+                    let mut next = combination.clone();
+                    next[i] += 1;
+                    if seen.insert(next.clone()) {
+                        frontier.push((cost_of(&next), next));
+                    }
+                }
+            }
+
+            let expr = node.join_recexprs(|id| {
+                children[usize::from(id)][combination[usize::from(id)]]
+                    .1
+                    .clone()
+            });
+            result.push((cost, expr));
+        }
+        result
+    }
+}
+
+// the tree cost of a NodeCost, which is what egg's Extractor optimizes
+struct TreeCost<'a, N>(&'a N);
+
+impl<N: NodeCost> CostFunction<MathExpression> for TreeCost<'_, N> {
+    type Cost = f64;
+
+    fn cost<C>(&mut self, enode: &MathExpression, mut costs: C) -> Self::Cost
+    where
+        C: FnMut(Id) -> Self::Cost,
+    {
+        enode.fold(self.0.node_cost(enode), |sum, id| sum + costs(id))
+    }
+}
+
+// extracts the representative of an e-class whose DAG cost (where every distinct subexpression is
+// paid for once, however often it occurs) is lowest, rather than its tree cost. Finding the
+// optimum is NP-hard, so this is a heuristic: the better of greedy tree and greedy DAG
+// extraction, refined by switching one class's node at a time for as long as that helps. It's
+// never worse than egg's Extractor with the same node costs.
+pub struct DagExtractor<'a, N: NodeCost> {
+    egraph: &'a JuniperEGraph,
+    node_cost: N,
+}
+
+impl<'a, N: NodeCost> DagExtractor<'a, N> {
+    pub fn new(egraph: &'a JuniperEGraph, node_cost: N) -> Self {
+        Self { egraph, node_cost }
+    }
+
+    // the extracted expression (with shared subexpressions only occurring once) and its DAG cost
+    pub fn find_best(&self, eclass: Id) -> (f64, RecExpr<MathExpression>) {
+        let root = self.egraph.find(eclass);
+
+        let tree = self.tree_choices();
+        let mut choices = match self.greedy_dag_choices() {
+            dag if self.dag_cost(&dag, root) < self.dag_cost(&tree, root) => dag,
+            _ => tree,
+        };
+        let mut cost = self.dag_cost(&choices, root);
+
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for class in self.reachable(&choices, root) {
+                for node in &self.egraph[class].nodes {
+                    let previous = choices.insert(class, node.clone());
+                    let switched = self.dag_cost(&choices, root);
+                    if switched < cost {
+                        cost = switched;
+                        improved = true;
+                    } else if let Some(previous) = previous {
+                        choices.insert(class, previous);
+                    }
+                }
+            }
+        }
+
+        (cost, self.build(&choices, root))
+    }
+
+    fn tree_choices(&self) -> HashMap<Id, MathExpression> {
+        let extractor = Extractor::new(self.egraph, TreeCost(&self.node_cost));
+        self.egraph
+            .classes()
+            .map(|class| (class.id, extractor.find_best_node(class.id).clone()))
+            .collect()
+    }
+
+    // every class picks the node whose subexpressions' classes cost least in total, counting
+    // each class once
+    fn greedy_dag_choices(&self) -> HashMap<Id, MathExpression> {
+        let mut best: HashMap<Id, (f64, HashMap<Id, f64>, MathExpression)> = HashMap::new();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for class in self.egraph.classes() {
+                for node in &class.nodes {
+                    let mut paid = HashMap::from([(class.id, self.node_cost.node_cost(node))]);
+                    let complete = node.children().iter().all(|child| {
+                        match best.get(&self.egraph.find(*child)) {
+                            // a class can't be its own subexpression
+                            Some((_, child_paid, _)) if !child_paid.contains_key(&class.id) => {
+                                paid.extend(child_paid);
+                                true
+                            }
+                            _ => false,
+                        }
+                    });
+                    if !complete {
+                        continue;
+                    }
+
+                    let cost = paid.values().sum::<f64>();
+                    if best
+                        .get(&class.id)
+                        .is_none_or(|(current, _, _)| cost < *current)
+                    {
+                        best.insert(class.id, (cost, paid, node.clone()));
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        best.into_iter()
+            .map(|(class, (_, _, node))| (class, node))
+            .collect()
+    }
+
+    // the classes used by the expression the choices make, or None if they're cyclic
+    fn reachable_checked(
+        &self,
+        choices: &HashMap<Id, MathExpression>,
+        root: Id,
+    ) -> Option<Vec<Id>> {
+        fn visit(
+            egraph: &JuniperEGraph,
+            choices: &HashMap<Id, MathExpression>,
+            class: Id,
+            visiting: &mut HashSet<Id>,
+            visited: &mut HashSet<Id>,
+            order: &mut Vec<Id>,
+        ) -> bool {
+            if visited.contains(&class) {
+                return true;
+            }
+            if !visiting.insert(class) {
+                return false;
+            }
+            let acyclic = match choices.get(&class) {
+                Some(node) => node.children().iter().all(|child| {
+                    visit(
+                        egraph,
+                        choices,
+                        egraph.find(*child),
+                        visiting,
+                        visited,
+                        order,
+                    )
+                }),
+                None => false,
+            };
+            visiting.remove(&class);
+            visited.insert(class);
+            order.push(class);
+            acyclic
+        }
+
+        let mut order = Vec::new();
+        let acyclic = visit(
+            self.egraph,
+            choices,
+            root,
+            &mut HashSet::new(),
+            &mut HashSet::new(),
+            &mut order,
+        );
+        acyclic.then_some(order)
+    }
+
+    fn reachable(&self, choices: &HashMap<Id, MathExpression>, root: Id) -> Vec<Id> {
+        self.reachable_checked(choices, root).unwrap_or_default()
+    }
+
+    fn dag_cost(&self, choices: &HashMap<Id, MathExpression>, root: Id) -> f64 {
+        match self.reachable_checked(choices, root) {
+            Some(classes) => classes
+                .iter()
+                .map(|class| self.node_cost.node_cost(&choices[class]))
+                .sum(),
+            None => f64::INFINITY,
+        }
+    }
+
+    fn build(&self, choices: &HashMap<Id, MathExpression>, root: Id) -> RecExpr<MathExpression> {
+        let mut expr = RecExpr::default();
+        let mut ids = HashMap::new();
+        // reachable classes are in post-order, so children are always added first
+        for class in self.reachable(choices, root) {
+            let node = choices[&class]
+                .clone()
+                .map_children(|child| ids[&self.egraph.find(child)]);
+            ids.insert(class, expr.add(node));
+        }
+        expr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use egg::rewrite;
+
+    use super::{Forbidden, NoRepresentativeError};
+    use crate::{Operator, Simplifier, WeightedCost};
+
+    #[test]
+    fn test_forbidden() -> Result<()> {
+        let forbidden: Forbidden = "sqrt x sin".parse()?;
+
+        assert_eq!(
+            forbidden,
+            Forbidden::new()
+                .with_operator(Operator::Sin)
+                .with_operator(Operator::Sqrt)
+                .with_variable('x')
+        );
+        assert_eq!(forbidden.to_string(), "sqrt, sin, x");
+        assert!("tan".parse::<Forbidden>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplify_avoiding() -> Result<()> {
+        let simplifier = Simplifier::new(
+            [
+                rewrite!("sin_sq"; "(^ (sin ?a) 2)" <=> "(- 1 (^ (cos ?a) 2))"),
+                rewrite!("sqrt_pow"; "(sqrt ?a)" <=> "(^ ?a 1/2)"),
+            ]
+            .concat(),
+        );
+
+        // the unconstrained simplification is the smaller one, but cos can be asked for
+        let expr = "(^ (sin y) 2)".parse()?;
+        assert_eq!(simplifier.simplify(&expr).best.to_string(), "(^ (sin y) 2)");
+        let only_cos = simplifier.simplify_avoiding(&expr, &"sin".parse()?)?;
+        assert_eq!(only_cos.best.to_string(), "(- 1 (^ (cos y) 2))");
+        assert_eq!(only_cos.cost, 6);
+
+        let no_sqrt = simplifier.simplify_avoiding(&"(sqrt 2)".parse()?, &"sqrt".parse()?)?;
+        assert_eq!(no_sqrt.best.to_string(), "(^ 2 1/2)");
+
+        // but y can't be eliminated
+        let error = simplifier
+            .simplify_avoiding(&expr, &"sin y".parse()?)
+            .unwrap_err();
+        let error = error.downcast_ref::<NoRepresentativeError>().unwrap();
+        assert_eq!(error.forbidden.to_string(), "sin, y");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dag_extraction() -> Result<()> {
+        let simplifier = Simplifier::new(rewrite!("square"; "(^ ?a 2)" <=> "(* ?a ?a)"));
+        let expr = "(^ (sin (+ x y)) 2)".parse()?;
+
+        // as a tree, repeating the sine costs more than squaring it, but not as a DAG
+        let tree = simplifier.simplify(&expr);
+        assert_eq!(tree.best.to_string(), "(^ (sin (+ x y)) 2)");
+        assert_eq!(tree.cost, 6);
+
+        let dag = simplifier.simplify_dag(&expr);
+        assert_eq!(dag.best.to_string(), "(* (sin (+ x y)) (sin (+ x y)))");
+        assert_eq!(dag.best.as_ref().len(), 5);
+        assert_eq!(dag.cost, 5.0);
+
+        // which is a matter of the node costs
+        let dag = simplifier.simplify_dag_with(
+            &expr,
+            WeightedCost::uniform().with_weight(Operator::Mul, 3.0)?,
+        );
+        assert_eq!(dag.best.to_string(), "(^ (sin (+ x y)) 2)");
+        assert_eq!(dag.cost, 6.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_k_best() -> Result<()> {
+        let simplifier = Simplifier::new(vec![rewrite!("mul_one"; "(* ?a 1)" => "?a")]);
+        let expr = "(+ (+ (* a 1) (* b 1)) (+ (* c 1) (* d 1)))".parse()?;
+
+        // each leaf is one of a, (* a 1), (* (* a 1) 1), ..., of sizes 1, 3, 5, ..., so there's one
+        // tree of size 7, then 4 of size 9, 10 of size 11, 20 of size 13 and 35 of size 15
+        let alternatives = simplifier.alternatives(&expr, 50);
+        let mut counts = Vec::<(usize, usize)>::new();
+        for (cost, _) in &alternatives {
+            match counts.last_mut() {
+                Some((last, count)) if last == cost => *count += 1,
+                _ => counts.push((*cost, 1)),
+            }
+        }
+        assert_eq!(counts, [(7, 1), (9, 4), (11, 10), (13, 20), (15, 15)]);
+        assert_eq!(alternatives[0].1.to_string(), "(+ (+ a b) (+ c d))");
+
+        let mut forms = alternatives
+            .iter()
+            .map(|(_, expr)| expr.to_string())
+            .collect::<Vec<_>>();
+        forms.sort();
+        forms.dedup();
+        assert_eq!(forms.len(), 50);
+
+        Ok(())
+    }
+}
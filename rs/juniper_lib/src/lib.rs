@@ -1,62 +1,425 @@
-use anyhow::Result;
-use egg::{Id, RecExpr, Rewrite, Runner};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use egg::{EGraph, Id, RecExpr, Rewrite, Runner};
 use juniper_lean_to_rewrite::JuniperJsonEntry;
-pub use juniper_math_expression::{approximate, ConstantFold, MathExpression};
+pub use juniper_lean_to_rewrite::{
+    CompiledRule, ConversionReport, ConversionStage, RuleCondition, SortEmbedding, TheoremReport,
+};
+pub use juniper_math_expression::{
+    approximate, approximate_with, ConstantFold, JuniperBigRational, MathExpression, Sort,
+};
+use lean_parse::lean_expr::Name;
+use serde::{Deserialize, Serialize};
+
+mod cost;
+pub use cost::{NodeCost, Operator, VariableOccurrences, WeightedCost};
+
+mod counterexample;
+pub use counterexample::{CounterexampleSearch, NumericCheck};
+
+mod extract;
+pub use extract::{
+    Candidates, Constrained, DagExtractor, Forbidden, Forbidding, KBestExtractor,
+    NoRepresentativeError,
+};
+
+mod linear;
+pub use linear::{solve_linear_system, LinearSolution};
+
+mod polynomial;
+pub use polynomial::{collect, expand, Atom, Monomial, Polynomial};
+
+mod prove;
+pub use prove::Proof;
 
+mod simplifier;
+pub use simplifier::{Simplification, Simplifier};
+
+mod solve;
+pub use solve::{solve, SideCondition, Solution, SolutionSet};
+
+pub type JuniperEGraph = EGraph<MathExpression, ConstantFold>;
 pub type JuniperRunner = Runner<MathExpression, ConstantFold>;
 pub type JuniperRewrite = Rewrite<MathExpression, ConstantFold>;
 
+// Rat theorems which the export marks as field identities (with `@[juniper_json field]`) are
+// generalized to (real-valued) terms. Any other Rat theorem only applies to rational terms
+pub const DEFAULT_EMBEDDINGS: &[SortEmbedding] = &[SortEmbedding {
+    from: Sort::Rat,
+    to: Sort::Real,
+    field_only: true,
+}];
+
+// the theorems exported when this crate was built
+pub const EMBEDDED_EXPORT: &str = include_str!("../../../exported.json");
+
+// a theorem name occurring more than once in a theorem set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateTheoremError {
+    pub name: Name,
+}
+
+impl Display for DuplicateTheoremError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "duplicate theorem: {}", self.name)
+    }
+}
+
+impl std::error::Error for DuplicateTheoremError {}
+
+// a set of exported theorems (in the format of exported.json) with distinct names, which rules
+// are converted from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TheoremSet {
+    theorems: Vec<JuniperJsonEntry>,
+}
+
+impl TheoremSet {
+    pub fn new(theorems: Vec<JuniperJsonEntry>) -> Result<Self> {
+        let mut set = Self::default();
+        set.merge(Self { theorems })?;
+        Ok(set)
+    }
+
+    pub fn embedded() -> Result<Self> {
+        Self::from_json_str(EMBEDDED_EXPORT)
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        Self::new(serde_json::from_str(json)?)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Self::new(serde_json::from_reader(reader)?)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("couldn't open theorem file {}", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+            .with_context(|| format!("couldn't load theorem file {}", path.display()))
+    }
+
+    // add the theorems of other to this set, failing (and leaving this set unchanged) if any of
+    // their names is already taken
+    pub fn merge(&mut self, other: TheoremSet) -> Result<()> {
+        let mut names: HashSet<&Name> = self.names().collect();
+        if let Some(duplicate) = other.names().find(|name| !names.insert(name)) {
+            return Err(DuplicateTheoremError {
+                name: duplicate.clone(),
+            }
+            .into());
+        }
+
+        self.theorems.extend(other.theorems);
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &Name> {
+        self.theorems.iter().map(JuniperJsonEntry::name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.theorems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.theorems.is_empty()
+    }
+
+    pub fn rules(&self) -> Result<Vec<JuniperRewrite>> {
+        juniper_lean_to_rewrite::lean_to_rewrites(self.theorems.clone(), DEFAULT_EMBEDDINGS)
+    }
+
+    // converts every theorem that can be, along with a report on the ones that couldn't be
+    pub fn rules_lenient(&self) -> (Vec<JuniperRewrite>, ConversionReport) {
+        juniper_lean_to_rewrite::lean_to_rewrites_lenient(self.theorems.clone(), DEFAULT_EMBEDDINGS)
+    }
+
+    pub fn compiled_rules_lenient(&self) -> (Vec<CompiledRule>, ConversionReport) {
+        juniper_lean_to_rewrite::lean_to_compiled_rules_lenient(
+            self.theorems.clone(),
+            DEFAULT_EMBEDDINGS,
+        )
+    }
+}
+
+// bump whenever the transpiler's output (or the cache format) changes, to invalidate old caches
+const RULE_CACHE_VERSION: u32 = 5;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// 64-bit FNV-1a, which (unlike the std hashers) is stable across runs and Rust versions
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// the converted (lenient) rules of a set of exports, which can be saved and loaded again without
+// re-running the transpiler, along with a content hash of the exports they were converted from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleCache {
+    version: u32,
+    source_hash: u64,
+    rules: Vec<CompiledRule>,
+    report: ConversionReport,
+}
+
+impl RuleCache {
+    // a hash of the given exports (in order) and the embeddings they're converted with
+    pub fn source_hash(sources: &[&str]) -> u64 {
+        let hash = fnv1a(
+            FNV_OFFSET_BASIS,
+            format!("{DEFAULT_EMBEDDINGS:?}").as_bytes(),
+        );
+        sources.iter().fold(hash, |hash, source| {
+            let hash = fnv1a(hash, &(source.len() as u64).to_le_bytes());
+            fnv1a(hash, source.as_bytes())
+        })
+    }
+
+    // transpile the given exports, which are merged into a single theorem set
+    pub fn build(sources: &[&str]) -> Result<Self> {
+        let mut theorems = TheoremSet::default();
+        for source in sources {
+            theorems.merge(TheoremSet::from_json_str(source)?)?;
+        }
+        let (rules, report) = theorems.compiled_rules_lenient();
+
+        Ok(Self {
+            version: RULE_CACHE_VERSION,
+            source_hash: Self::source_hash(sources),
+            rules,
+            report,
+        })
+    }
+
+    // whether this cache was built from exactly the given exports (by this version of Juniper)
+    pub fn is_fresh(&self, sources: &[&str]) -> bool {
+        self.version == RULE_CACHE_VERSION && self.source_hash == Self::source_hash(sources)
+    }
+
+    pub fn compiled_rules(&self) -> &[CompiledRule] {
+        &self.rules
+    }
+
+    pub fn report(&self) -> &ConversionReport {
+        &self.report
+    }
+
+    pub fn rules(&self) -> Result<Vec<JuniperRewrite>> {
+        self.rules
+            .iter()
+            .map(|rule| {
+                rule.to_rewrite()
+                    .with_context(|| format!("couldn't build cached rule {}", rule.name))
+            })
+            .collect()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("couldn't open rule cache {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("couldn't load rule cache {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("couldn't create rule cache {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .with_context(|| format!("couldn't write rule cache {}", path.display()))
+    }
+
+    // the cache at path if it's up to date with the given exports, otherwise a freshly built one
+    // (which replaces whatever was at path, if it can be written; the cache is only an optimization,
+    // so failing to save it isn't an error)
+    pub fn load_or_build(sources: &[&str], path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Ok(cache) = Self::load(path) {
+            if cache.is_fresh(sources) {
+                return Ok(cache);
+            }
+        }
+
+        let cache = Self::build(sources)?;
+        if let Err(e) = cache.save(path) {
+            eprintln!("{e:#}");
+        }
+        Ok(cache)
+    }
+}
+
 pub fn get_juniper_rules() -> Result<Vec<JuniperRewrite>> {
-    let lean_theorems: Vec<JuniperJsonEntry> =
-        serde_json::from_str(include_str!("../../../exported.json"))?;
-    Ok(juniper_lean_to_rewrite::lean_to_rewrites(lean_theorems)?)
+    TheoremSet::embedded()?.rules()
+}
+
+// loads every rule that can be converted, along with a report on the theorems that couldn't be
+pub fn get_juniper_rules_lenient() -> Result<(Vec<JuniperRewrite>, ConversionReport)> {
+    Ok(TheoremSet::embedded()?.rules_lenient())
+}
+
+// like get_juniper_rules_lenient, but only transpiles the embedded theorems if the rule cache at
+// cache_path is missing or out of date
+pub fn get_juniper_rules_cached(
+    cache_path: impl AsRef<Path>,
+) -> Result<(Vec<JuniperRewrite>, ConversionReport)> {
+    let cache = RuleCache::load_or_build(&[EMBEDDED_EXPORT], cache_path)?;
+    Ok((cache.rules()?, cache.report().clone()))
 }
 
 pub fn is_atomic(re: &RecExpr<MathExpression>, id: &Id) -> bool {
-    match &re[*id] {
-        MathExpression::Constant(_) => true,
-        MathExpression::Variable(_) => true,
-        _ => false,
-    }
+    matches!(
+        &re[*id],
+        MathExpression::Constant(_) | MathExpression::Variable(_)
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use egg::{AstSize, Extractor};
 
-    use crate::{get_juniper_rules, JuniperRunner};
+    use crate::{get_juniper_rules, DuplicateTheoremError, RuleCache, Simplifier, TheoremSet};
+
+    fn theorem_json(name: &str) -> String {
+        format!(
+            r#"{{"name": "{name}", "type": {}}}"#,
+            include_str!("../../test_assets/add_zero.json")
+        )
+    }
+
+    fn field_theorem_json(name: &str) -> String {
+        format!(
+            r#"{{"name": "{name}", "type": {}, "field": true}}"#,
+            include_str!("../../test_assets/add_zero.json")
+        )
+    }
 
     #[test]
     fn test_default_rules() -> Result<()> {
-        let rules = get_juniper_rules()?;
+        let simplifier = Simplifier::new(get_juniper_rules()?);
+
+        let neg_add_cancel = simplifier.simplify_str("(+ (- a) a)")?;
+
+        assert_eq!(neg_add_cancel.best, "0".parse()?);
+
+        let sin_sq_cos_sq = simplifier.simplify_str("(+ (^ (sin x) 2) (^ (cos x) 2))")?;
+
+        assert_eq!(sin_sq_cos_sq.best, "1".parse()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_theorems() -> Result<()> {
+        let json = format!("[{}]", theorem_json("Rat.add_zero"));
+        let set = TheoremSet::from_json_str(&json)?;
+
+        assert_eq!(set.len(), 1);
+        assert!(!set.rules()?.is_empty());
+        assert_eq!(TheoremSet::from_reader(json.as_bytes())?, set);
+
+        let error = TheoremSet::from_path("does_not_exist.json").unwrap_err();
+        assert!(error.to_string().contains("does_not_exist.json"));
+
+        Ok(())
+    }
 
-        let neg_add_cancel = "(+ (- a) a)".parse()?;
-        let neg_add_cancel_runner = JuniperRunner::default()
-            .with_expr(&neg_add_cancel)
-            .run(&rules);
+    #[test]
+    fn test_default_embeddings() -> Result<()> {
+        let simplify = |theorem: &str, expr: &str| -> Result<String> {
+            let set = TheoremSet::from_json_str(&format!("[{theorem}]"))?;
+            Ok(Simplifier::new(set.rules()?)
+                .simplify_str(expr)?
+                .best
+                .to_string())
+        };
 
-        let neg_add_cancel_extractor = Extractor::new(&neg_add_cancel_runner.egraph, AstSize);
-        let (_, neg_add_cancel_extracted) =
-            neg_add_cancel_extractor.find_best(neg_add_cancel_runner.roots[0]);
+        // a Rat theorem marked as a field identity holds for real terms too
+        let add_zero = field_theorem_json("Rat.add_zero");
+        assert_eq!(simplify(&add_zero, "(+ (sin x) 0)")?, "(sin x)");
+        // but one which isn't marked only applies to rational terms, whatever its name
+        let add_zero = theorem_json("Rat.add_zero");
+        assert_eq!(simplify(&add_zero, "(+ (sin x) 0)")?, "(+ (sin x) 0)");
+        assert_eq!(simplify(&add_zero, "(+ (inv 3) 0)")?, "(inv 3)");
 
-        let neg_add_cancel_manual = "0".parse()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_theorems() -> Result<()> {
+        let mut set = TheoremSet::from_json_str(&format!("[{}]", theorem_json("a")))?;
+        set.merge(TheoremSet::from_json_str(&format!(
+            "[{}]",
+            theorem_json("b")
+        ))?)?;
+        assert_eq!(
+            set.names().map(|n| n.to_string()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
 
-        assert_eq!(neg_add_cancel_extracted, neg_add_cancel_manual);
+        // a clash leaves the set as it was
+        let clash =
+            TheoremSet::from_json_str(&format!("[{}, {}]", theorem_json("c"), theorem_json("a")))?;
+        let mut attempt = set.clone();
+        let error = attempt.merge(clash).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<DuplicateTheoremError>(),
+            Some(&DuplicateTheoremError { name: "a".into() })
+        );
+        assert_eq!(attempt, set);
+
+        // as do duplicates within a single file
+        assert!(TheoremSet::from_json_str(&format!(
+            "[{}, {}]",
+            theorem_json("a"),
+            theorem_json("a")
+        ))
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_cache() -> Result<()> {
+        let add_zero = format!("[{}]", theorem_json("Rat.add_zero"));
+        let renamed = format!("[{}]", theorem_json("Rat.add_zero'"));
+        let path =
+            std::env::temp_dir().join(format!("juniper_rule_cache_{}.json", std::process::id()));
 
-        let sin_sq_cos_sq = "(+ (^ (sin x) 2) (^ (cos x) 2))".parse()?;
-        let sin_sq_cos_sq_runner = JuniperRunner::default()
-            .with_expr(&sin_sq_cos_sq)
-            .run(&rules);
+        let cache = RuleCache::load_or_build(&[&add_zero], &path)?;
+        assert!(cache.is_fresh(&[&add_zero]));
+        assert!(!cache.is_fresh(&[&renamed]));
+        assert!(!cache.is_fresh(&[&add_zero, &add_zero]));
+        assert_eq!(cache.rules()?.len(), cache.compiled_rules().len());
 
-        let sin_sq_cos_sq_extractor = Extractor::new(&sin_sq_cos_sq_runner.egraph, AstSize);
-        let (_, sin_sq_cos_sq_extracted) =
-            sin_sq_cos_sq_extractor.find_best(sin_sq_cos_sq_runner.roots[0]);
+        // the saved cache is reused as long as the export is unchanged
+        let loaded = RuleCache::load(&path)?;
+        assert_eq!(loaded.compiled_rules(), cache.compiled_rules());
+        assert!(RuleCache::load_or_build(&[&add_zero], &path)?.is_fresh(&[&add_zero]));
 
-        let sin_sq_cos_sq_manual = "1".parse()?;
+        // and replaced once it isn't
+        let rebuilt = RuleCache::load_or_build(&[&renamed], &path)?;
+        assert_eq!(rebuilt.compiled_rules()[0].theorem, "Rat.add_zero'");
+        assert!(RuleCache::load(&path)?.is_fresh(&[&renamed]));
 
-        assert_eq!(sin_sq_cos_sq_extracted, sin_sq_cos_sq_manual);
+        // a cache which can't be saved is still returned
+        let unwritable = path.join("rule_cache.json");
+        let unsaved = RuleCache::load_or_build(&[&add_zero], &unwritable)?;
+        assert!(unsaved.is_fresh(&[&add_zero]));
+        assert!(RuleCache::load(&unwritable).is_err());
 
+        std::fs::remove_file(path)?;
         Ok(())
     }
 }
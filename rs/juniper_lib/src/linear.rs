@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use anyhow::{Error, Result};
+use egg::{Id, RecExpr};
+use num::{BigInt, BigRational, Integer, One, Signed, Zero};
+
+use crate::solve::{add, constant, mul, rational, sub, subexpr, Expr};
+use crate::{JuniperBigRational, MathExpression};
+
+// the solutions of a system of linear equations
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinearSolution {
+    // every unknown has exactly one value
+    Unique(BTreeMap<char, JuniperBigRational>),
+    // the free unknowns can take any value, and the others are given in terms of them
+    Family {
+        values: BTreeMap<char, Expr>,
+        free: Vec<char>,
+    },
+    // no values satisfy every equation
+    Inconsistent,
+}
+
+impl Display for LinearSolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values = match self {
+            LinearSolution::Unique(values) => values
+                .iter()
+                .map(|(unknown, value)| format!("{unknown} = {value}"))
+                .collect::<Vec<_>>(),
+            LinearSolution::Family { values, free } => values
+                .iter()
+                .map(|(unknown, value)| format!("{unknown} = {value}"))
+                .chain(free.iter().map(|unknown| format!("{unknown} is free")))
+                .collect(),
+            LinearSolution::Inconsistent => return write!(f, "inconsistent"),
+        };
+        write!(f, "{}", values.join(", "))
+    }
+}
+
+// a linear combination of the unknowns plus a constant
+#[derive(Debug, Clone)]
+struct Affine {
+    coefficients: Vec<BigRational>,
+    constant: BigRational,
+}
+
+impl Affine {
+    fn constant(n: usize, constant: BigRational) -> Self {
+        Affine {
+            coefficients: vec![BigRational::zero(); n],
+            constant,
+        }
+    }
+
+    fn unknown(n: usize, i: usize) -> Self {
+        let mut affine = Affine::constant(n, BigRational::zero());
+        affine.coefficients[i] = BigRational::one();
+        affine
+    }
+
+    fn as_constant(&self) -> Option<&BigRational> {
+        self.coefficients
+            .iter()
+            .all(Zero::is_zero)
+            .then_some(&self.constant)
+    }
+
+    fn combine(self, other: Affine, f: impl Fn(BigRational, BigRational) -> BigRational) -> Self {
+        Affine {
+            coefficients: self
+                .coefficients
+                .into_iter()
+                .zip(other.coefficients)
+                .map(|(a, b)| f(a, b))
+                .collect(),
+            constant: f(self.constant, other.constant),
+        }
+    }
+
+    fn scale(self, k: &BigRational) -> Self {
+        Affine {
+            coefficients: self.coefficients.into_iter().map(|c| c * k).collect(),
+            constant: self.constant * k,
+        }
+    }
+}
+
+// an expression as an affine combination of the unknowns with rational coefficients, if it is one
+fn affine(expr: &Expr, id: Id, unknowns: &[char]) -> Option<Affine> {
+    let n = unknowns.len();
+    let x = |id: &Id| affine(expr, *id, unknowns);
+    Some(match &expr[id] {
+        MathExpression::Variable(v) => {
+            Affine::unknown(n, unknowns.iter().position(|unknown| unknown == v)?)
+        }
+        MathExpression::Add([a, b]) => x(a)?.combine(x(b)?, |a, b| a + b),
+        MathExpression::Sub([a, b]) => x(a)?.combine(x(b)?, |a, b| a - b),
+        MathExpression::Neg(a) => x(a)?.scale(&-BigRational::one()),
+        MathExpression::Mul([a, b]) => {
+            let (a, b) = (x(a)?, x(b)?);
+            match (a.as_constant(), b.as_constant()) {
+                (Some(k), _) => b.scale(k),
+                (_, Some(k)) => a.scale(k),
+                _ => return None,
+            }
+        }
+        MathExpression::Div([a, b]) => {
+            let divisor = x(b)?.as_constant().filter(|b| !b.is_zero())?.recip();
+            x(a)?.scale(&divisor)
+        }
+        // anything else has to be a rational constant (such as an inverse or a power of one)
+        _ => Affine::constant(n, rational(&subexpr(expr, id))?),
+    })
+}
+
+// the coefficients of the unknowns in an equation and its constant, as in a x + b y + … = c
+fn equation_row(equation: &Expr, unknowns: &[char]) -> Option<(Vec<BigRational>, BigRational)> {
+    let MathExpression::Eq([lhs, rhs]) = equation[equation.root()] else {
+        return None;
+    };
+    let difference =
+        affine(equation, lhs, unknowns)?.combine(affine(equation, rhs, unknowns)?, |a, b| a - b);
+    Some((difference.coefficients, -difference.constant))
+}
+
+// whether an equation is linear in the unknowns with rational coefficients
+pub(crate) fn is_linear(equation: &Expr, unknowns: &[char]) -> bool {
+    equation_row(equation, unknowns).is_some()
+}
+
+// reduce an integer matrix to row echelon form by fraction-free (Bareiss) elimination, which keeps
+// every entry an integer (a minor of the original matrix) without their sizes blowing up. Only the
+// first `columns` columns are pivoted on, and the pivot columns are returned in order
+fn eliminate(rows: &mut [Vec<BigInt>], columns: usize) -> Vec<usize> {
+    let mut pivots = Vec::new();
+    let mut previous = BigInt::one();
+    for column in 0..columns {
+        let r = pivots.len();
+        let Some(pivot) = (r..rows.len()).find(|&i| !rows[i][column].is_zero()) else {
+            continue;
+        };
+        rows.swap(r, pivot);
+
+        let (above, below) = rows.split_at_mut(r + 1);
+        let pivot_row = &above[r];
+        for row in below {
+            for j in column + 1..pivot_row.len() {
+                let entry = &pivot_row[column] * &row[j] - &row[column] * &pivot_row[j];
+                debug_assert!(entry.is_multiple_of(&previous));
+                row[j] = entry / &previous;
+            }
+            row[column] = BigInt::zero();
+        }
+
+        previous = rows[r][column].clone();
+        pivots.push(column);
+    }
+    pivots
+}
+
+fn variable(v: char) -> Expr {
+    let mut expr = RecExpr::default();
+    expr.add(MathExpression::Variable(v));
+    expr
+}
+
+// solve a system of equations (Eq expressions) which are linear in the unknowns with rational
+// coefficients, exactly
+pub fn solve_linear_system(equations: &[Expr], unknowns: &[char]) -> Result<LinearSolution> {
+    for (i, unknown) in unknowns.iter().enumerate() {
+        if unknowns[..i].contains(unknown) {
+            return Err(Error::msg(format!("{unknown} is declared more than once")));
+        }
+    }
+    let n = unknowns.len();
+
+    // the augmented matrix, with each row scaled to integers
+    let mut rows = Vec::new();
+    for (i, equation) in equations.iter().enumerate() {
+        let Some((coefficients, constant)) = equation_row(equation, unknowns) else {
+            let unknowns = unknowns.iter().map(char::to_string).collect::<Vec<_>>();
+            return Err(Error::msg(format!(
+                "equation {} ({equation}) isn't linear in {} with rational coefficients",
+                i + 1,
+                unknowns.join(", ")
+            )));
+        };
+        let entries = coefficients
+            .into_iter()
+            .chain([constant])
+            .collect::<Vec<_>>();
+        let denominator = entries
+            .iter()
+            .fold(BigInt::one(), |lcm, entry| lcm.lcm(entry.denom()));
+        rows.push(
+            entries
+                .iter()
+                .map(|entry| (entry * &denominator).to_integer())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let pivots = eliminate(&mut rows, n);
+    // below the pivots every coefficient is 0, so a non-zero constant there is 0 = c
+    if rows[pivots.len()..].iter().any(|row| !row[n].is_zero()) {
+        return Ok(LinearSolution::Inconsistent);
+    }
+
+    // back substitution, giving each pivot unknown in terms of the free ones
+    let mut values = (0..n).map(|i| Affine::unknown(n, i)).collect::<Vec<_>>();
+    for (r, &column) in pivots.iter().enumerate().rev() {
+        let row = &rows[r];
+        let mut value = Affine::constant(n, BigRational::from_integer(row[n].clone()));
+        for j in column + 1..n {
+            let term = values[j]
+                .clone()
+                .scale(&BigRational::from_integer(row[j].clone()));
+            value = value.combine(term, |a, b| a - b);
+        }
+        values[column] = value.scale(&BigRational::from_integer(row[column].clone()).recip());
+    }
+
+    let free = (0..n)
+        .filter(|i| !pivots.contains(i))
+        .map(|i| unknowns[i])
+        .collect::<Vec<_>>();
+    if free.is_empty() {
+        return Ok(LinearSolution::Unique(
+            pivots
+                .iter()
+                .map(|&i| (unknowns[i], JuniperBigRational(values[i].constant.clone())))
+                .collect(),
+        ));
+    }
+
+    // positive terms go first, so x = z - y rather than -y + z
+    let expression = |value: &Affine| {
+        let mut terms = value
+            .coefficients
+            .iter()
+            .zip(unknowns)
+            .filter(|(coefficient, _)| !coefficient.is_zero())
+            .collect::<Vec<_>>();
+        terms.sort_by_key(|(coefficient, _)| coefficient.is_negative());
+        terms.into_iter().fold(
+            constant(value.constant.clone()),
+            |sum, (coefficient, unknown)| {
+                let term = mul(&constant(coefficient.abs()), &variable(*unknown));
+                if coefficient.is_positive() {
+                    add(&sum, &term)
+                } else {
+                    sub(&sum, &term)
+                }
+            },
+        )
+    };
+    Ok(LinearSolution::Family {
+        values: pivots
+            .iter()
+            .map(|&i| (unknowns[i], expression(&values[i])))
+            .collect(),
+        free,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{solve_linear_system, LinearSolution};
+
+    fn solve(equations: &[&str], unknowns: &[char]) -> Result<LinearSolution> {
+        let equations = equations
+            .iter()
+            .map(|equation| equation.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        solve_linear_system(&equations, unknowns)
+    }
+
+    #[test]
+    fn test_linear_system() -> Result<()> {
+        let solution = solve(&["(= (+ x y) 3)", "(= (- x y) 1)"], &['x', 'y'])?;
+        assert_eq!(solution.to_string(), "x = 2, y = 1");
+
+        // fractional coefficients, and equations needing a row swap
+        let solution = solve(
+            &[
+                "(= (+ (* 0 x) (+ y z)) 2)",
+                "(= (+ (/ x 2) (- y z)) (/ 1 3))",
+                "(= (* 3 (+ x (* 2 z))) (- y 1))",
+            ],
+            &['x', 'y', 'z'],
+        )?;
+        assert_eq!(solution.to_string(), "x = -58/57, y = 27/19, z = 11/19");
+
+        let solution = solve(
+            &["(= (+ x (* 2 y)) 4)", "(= (* 2 (+ x (* 2 y))) 8)"],
+            &['x', 'y'],
+        )?;
+        assert_eq!(solution.to_string(), "x = (- 4 (* 2 y)), y is free");
+        let solution = solve(&["(= (+ x y) z)"], &['x', 'y', 'z'])?;
+        assert_eq!(solution.to_string(), "x = (- z y), y is free, z is free");
+
+        let solution = solve(&["(= (+ x y) 1)", "(= (+ y x) 2)"], &['x', 'y'])?;
+        assert_eq!(solution, LinearSolution::Inconsistent);
+
+        assert!(solve(&["(= (* x y) 1)"], &['x', 'y']).is_err());
+        assert!(solve(&["(= (+ x a) 1)"], &['x']).is_err());
+        assert!(solve(&["(+ x 1)"], &['x']).is_err());
+
+        Ok(())
+    }
+}
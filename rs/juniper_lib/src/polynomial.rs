@@ -0,0 +1,490 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use egg::{Id, Language, RecExpr};
+use num::{BigInt, BigRational, One, Signed, Zero};
+
+use crate::solve::{add, constant, mul, neg, node, pow, small_power, sub, Expr};
+use crate::{JuniperBigRational, MathExpression};
+
+// what polynomials are polynomials in: variables, and subterms which aren't polynomials (such as
+// (sin x)), which are told apart by how they print
+#[derive(Debug, Clone)]
+pub enum Atom {
+    Variable(char),
+    Term(Expr),
+}
+
+impl Atom {
+    pub fn to_expr(&self) -> Expr {
+        match self {
+            Atom::Variable(v) => {
+                let mut expr = RecExpr::default();
+                expr.add(MathExpression::Variable(*v));
+                expr
+            }
+            Atom::Term(expr) => expr.clone(),
+        }
+    }
+}
+
+// variables come before other terms
+impl Ord for Atom {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Atom::Variable(a), Atom::Variable(b)) => a.cmp(b),
+            (Atom::Variable(_), Atom::Term(_)) => Ordering::Less,
+            (Atom::Term(_), Atom::Variable(_)) => Ordering::Greater,
+            (Atom::Term(a), Atom::Term(b)) => a.to_string().cmp(&b.to_string()),
+        }
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Atom {}
+
+// a product of atoms raised to (positive) powers
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Monomial(BTreeMap<Atom, u32>);
+
+impl Monomial {
+    pub fn atom(atom: Atom) -> Self {
+        Monomial(BTreeMap::from([(atom, 1)]))
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.0.values().sum()
+    }
+
+    pub fn degree_in(&self, atom: &Atom) -> u32 {
+        self.0.get(atom).copied().unwrap_or(0)
+    }
+
+    pub fn powers(&self) -> impl Iterator<Item = (&Atom, u32)> {
+        self.0.iter().map(|(atom, power)| (atom, *power))
+    }
+
+    fn without(&self, atom: &Atom) -> Self {
+        let mut monomial = self.clone();
+        monomial.0.remove(atom);
+        monomial
+    }
+
+    fn product(&self, other: &Self) -> Self {
+        let mut monomial = self.clone();
+        for (atom, power) in other.powers() {
+            *monomial.0.entry(atom.clone()).or_default() += power;
+        }
+        monomial
+    }
+
+    // graded lexicographic order, with the highest degree (and then the most of the first atom)
+    // first, so x^2 + xy + y^2 + x + 1
+    fn graded(&self, other: &Self) -> Ordering {
+        let mut atoms = self.0.keys().chain(other.0.keys()).collect::<Vec<_>>();
+        atoms.sort();
+        Reverse(self.degree())
+            .cmp(&Reverse(other.degree()))
+            .then_with(|| {
+                atoms
+                    .into_iter()
+                    .map(|atom| other.degree_in(atom).cmp(&self.degree_in(atom)))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+
+    // None for the empty product
+    fn to_expr(&self) -> Option<Expr> {
+        self.powers()
+            .map(|(atom, power)| pow(&atom.to_expr(), BigRational::from_integer(power.into())))
+            .reduce(|product, factor| mul(&product, &factor))
+    }
+}
+
+// powers are only multiplied out while they'd have at most this many terms, so that something
+// like (^ (+ x y z) 1000) stays a power too
+const MAX_TERMS: usize = 1000;
+
+// the most terms the n-th power of a polynomial with the given number of terms can have (the
+// number of monomials of degree n in that many atoms), stopping once it's over MAX_TERMS
+fn power_terms(terms: usize, n: u32) -> usize {
+    let mut count: u128 = 1;
+    for i in 1..terms as u128 {
+        count = count * (u128::from(n) + i) / i;
+        if count > MAX_TERMS as u128 {
+            break;
+        }
+    }
+    count as usize
+}
+
+// a sparse multivariate polynomial with rational coefficients (none of which are zero)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Polynomial {
+    terms: BTreeMap<Monomial, JuniperBigRational>,
+}
+
+impl Polynomial {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn constant(c: JuniperBigRational) -> Self {
+        let mut polynomial = Self::zero();
+        polynomial.insert(Monomial::default(), c.0);
+        polynomial
+    }
+
+    pub fn atom(atom: Atom) -> Self {
+        let mut polynomial = Self::zero();
+        polynomial.insert(Monomial::atom(atom), BigRational::one());
+        polynomial
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // the polynomial's value, if it doesn't depend on any atom
+    pub fn as_constant(&self) -> Option<JuniperBigRational> {
+        match self.terms.iter().collect::<Vec<_>>().as_slice() {
+            [] => Some(JuniperBigRational(BigRational::zero())),
+            [(monomial, c)] if monomial.0.is_empty() => Some((*c).clone()),
+            _ => None,
+        }
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = (&Monomial, &JuniperBigRational)> {
+        self.terms.iter()
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.terms.keys().map(Monomial::degree).max().unwrap_or(0)
+    }
+
+    fn insert(&mut self, monomial: Monomial, c: BigRational) {
+        let sum = match self.terms.remove(&monomial) {
+            Some(existing) => existing.0 + c,
+            None => c,
+        };
+        if !sum.is_zero() {
+            self.terms.insert(monomial, JuniperBigRational(sum));
+        }
+    }
+
+    fn scale(&self, k: &BigRational) -> Self {
+        let mut polynomial = Self::zero();
+        for (monomial, c) in self.terms() {
+            polynomial.insert(monomial.clone(), &c.0 * k);
+        }
+        polynomial
+    }
+
+    // by repeated squaring
+    pub fn pow(&self, mut n: u32) -> Self {
+        let mut result = Self::constant(JuniperBigRational(BigRational::one()));
+        let mut base = self.clone();
+        while n > 0 {
+            if n % 2 == 1 {
+                result = &result * &base;
+            }
+            n /= 2;
+            if n > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    // whether the n-th power is small enough to multiply out, in its number of terms as well as
+    // its degree and coefficients
+    fn small_power(&self, n: u32) -> bool {
+        power_terms(self.terms.len(), n) <= MAX_TERMS
+            && small_power(
+                self.terms.values().map(|c| &c.0),
+                self.degree(),
+                &BigInt::from(n),
+            )
+    }
+
+    // an expression as a polynomial, multiplying out products and whole number powers. Anything
+    // else becomes an atom (after its own subexpressions are expanded, so that (sin (+ x 1)) and
+    // (sin (+ 1 x)) are the same atom)
+    pub fn from_expr(expr: &Expr) -> Self {
+        Self::from_subexpr(expr, expr.root())
+    }
+
+    fn from_subexpr(expr: &Expr, id: Id) -> Self {
+        let x = |id: &Id| Self::from_subexpr(expr, *id);
+        let constant = |polynomial: &Polynomial| polynomial.as_constant().map(|c| c.0);
+        let opaque = |children: &[Polynomial]| {
+            let children = children.iter().map(Polynomial::to_expr).collect::<Vec<_>>();
+            let children = children.iter().collect::<Vec<_>>();
+            Self::atom(Atom::Term(node(expr[id].clone(), &children)))
+        };
+
+        match &expr[id] {
+            MathExpression::Constant(c) => Self::constant(c.clone()),
+            MathExpression::Variable(v) => Self::atom(Atom::Variable(*v)),
+            MathExpression::Add([a, b]) => x(a) + x(b),
+            MathExpression::Sub([a, b]) => x(a) - x(b),
+            MathExpression::Mul([a, b]) => &x(a) * &x(b),
+            MathExpression::Neg(a) => -x(a),
+            MathExpression::Div([a, b]) => {
+                let (a, b) = (x(a), x(b));
+                match constant(&b).filter(|b| !b.is_zero()) {
+                    Some(b) => a.scale(&b.recip()),
+                    None => opaque(&[a, b]),
+                }
+            }
+            MathExpression::Inv(a) => {
+                let a = x(a);
+                match constant(&a).filter(|a| !a.is_zero()) {
+                    Some(a) => Self::constant(JuniperBigRational(a.recip())),
+                    None => opaque(&[a]),
+                }
+            }
+            MathExpression::Pow([a, b]) => {
+                let (a, b) = (x(a), x(b));
+                let exponent = constant(&b)
+                    .filter(BigRational::is_integer)
+                    .map(|n| n.to_integer());
+                match (exponent, constant(&a)) {
+                    (Some(n), _) if !n.is_negative() => match u32::try_from(&n) {
+                        Ok(n) if a.small_power(n) => a.pow(n),
+                        _ => opaque(&[a, b]),
+                    },
+                    (Some(n), Some(base)) if !base.is_zero() && small_power([&base], 0, &n) => {
+                        Self::constant(JuniperBigRational(num::pow::Pow::pow(&base, &n)))
+                    }
+                    _ => opaque(&[a, b]),
+                }
+            }
+            node => opaque(&node.children().iter().map(x).collect::<Vec<_>>()),
+        }
+    }
+
+    // the terms in graded order, negating them to be subtracted where their coefficients are
+    fn sum(mut terms: Vec<(Monomial, Expr, bool)>) -> Expr {
+        terms.sort_by(|(a, ..), (b, ..)| a.graded(b));
+        terms
+            .into_iter()
+            .fold(None, |sum: Option<Expr>, (_, term, negative)| {
+                Some(match (sum, negative) {
+                    (None, false) => term,
+                    (None, true) => neg(&term),
+                    (Some(sum), false) => add(&sum, &term),
+                    (Some(sum), true) => sub(&sum, &term),
+                })
+            })
+            .unwrap_or_else(|| constant(BigRational::zero()))
+    }
+
+    pub fn to_expr(&self) -> Expr {
+        Self::sum(
+            self.terms()
+                .map(|(monomial, c)| {
+                    let coefficient = constant(c.0.abs());
+                    let term = match monomial.to_expr() {
+                        Some(product) => mul(&coefficient, &product),
+                        None => coefficient,
+                    };
+                    (monomial.clone(), term, c.0.is_negative())
+                })
+                .collect(),
+        )
+    }
+
+    // the polynomial as a sum of powers of the variable, each multiplied by a polynomial in
+    // everything else. Atoms which only contain the variable (such as (sin x)) aren't powers of it
+    pub fn collect(&self, var: char) -> Expr {
+        let var = Atom::Variable(var);
+        let mut coefficients: BTreeMap<u32, Polynomial> = BTreeMap::new();
+        for (monomial, c) in self.terms() {
+            coefficients
+                .entry(monomial.degree_in(&var))
+                .or_default()
+                .insert(monomial.without(&var), c.0.clone());
+        }
+
+        Self::sum(
+            coefficients
+                .into_iter()
+                .map(|(power, coefficient)| {
+                    // a coefficient is subtracted when its leading term is negative
+                    let leading = coefficient
+                        .terms()
+                        .min_by(|(a, _), (b, _)| a.graded(b))
+                        .map(|(_, c)| c.0.clone())
+                        .unwrap_or_default();
+                    let coefficient = if leading.is_negative() {
+                        -coefficient
+                    } else {
+                        coefficient
+                    };
+
+                    let monomial = Monomial(
+                        (power > 0)
+                            .then(|| (var.clone(), power))
+                            .into_iter()
+                            .collect(),
+                    );
+                    let term = match monomial.to_expr() {
+                        Some(power) => mul(&coefficient.to_expr(), &power),
+                        None => coefficient.to_expr(),
+                    };
+                    (monomial, term, leading.is_negative())
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_expr())
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Polynomial;
+
+    fn add(mut self, other: Polynomial) -> Polynomial {
+        for (monomial, c) in other.terms {
+            self.insert(monomial, c.0);
+        }
+        self
+    }
+}
+
+impl Neg for Polynomial {
+    type Output = Polynomial;
+
+    fn neg(self) -> Polynomial {
+        self.scale(&-BigRational::one())
+    }
+}
+
+impl Sub for Polynomial {
+    type Output = Polynomial;
+
+    fn sub(self, other: Polynomial) -> Polynomial {
+        self + -other
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: &Polynomial) -> Polynomial {
+        let mut product = Polynomial::zero();
+        for (a, c) in self.terms() {
+            for (b, d) in other.terms() {
+                product.insert(a.product(b), &c.0 * &d.0);
+            }
+        }
+        product
+    }
+}
+
+// multiply out products and whole number powers, and combine like terms
+pub fn expand(expr: &Expr) -> Expr {
+    Polynomial::from_expr(expr).to_expr()
+}
+
+// expand, then group the terms by their power of the variable
+pub fn collect(expr: &Expr, var: char) -> Expr {
+    Polynomial::from_expr(expr).collect(var)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use num::BigRational;
+
+    use super::{collect, expand, power_terms, Atom, Monomial, Polynomial};
+
+    fn expanded(expr: &str) -> Result<String> {
+        Ok(expand(&expr.parse()?).to_string())
+    }
+
+    #[test]
+    fn test_expand() -> Result<()> {
+        assert_eq!(expanded("(^ (+ x 1) 2)")?, "(+ (+ (^ x 2) (* 2 x)) 1)");
+        assert_eq!(expanded("(* (- x y) (+ x y))")?, "(- (^ x 2) (^ y 2))");
+        assert_eq!(
+            expanded("(^ (+ (sin x) 1) 2)")?,
+            "(+ (+ (^ (sin x) 2) (* 2 (sin x))) 1)"
+        );
+        assert_eq!(expanded("(- (sin (+ x 1)) (sin (+ 1 x)))")?, "0");
+        assert_eq!(expanded("(/ (- 1 x) 2)")?, "(+ (* -1/2 x) 1/2)");
+        assert_eq!(
+            expanded("(* (^ x -1) (+ x 1))")?,
+            "(+ (* x (^ x -1)) (^ x -1))"
+        );
+
+        let polynomial = Polynomial::from_expr(&"(^ (+ x 1) 5)".parse()?);
+        assert_eq!(polynomial.degree(), 5);
+        assert_eq!(polynomial.terms().count(), 6);
+        let x_squared =
+            Monomial::atom(Atom::Variable('x')).product(&Monomial::atom(Atom::Variable('x')));
+        let (_, c) = polynomial
+            .terms()
+            .find(|(monomial, _)| **monomial == x_squared)
+            .unwrap();
+        assert_eq!(c.0, BigRational::from_integer(10.into()));
+
+        // powers with too many terms, or too big an exponent, aren't multiplied out
+        assert_eq!(expanded("(^ (+ x y) 1000000)")?, "(^ (+ x y) 1000000)");
+        assert_eq!(
+            expanded("(^ (+ x (+ y z)) 1000)")?,
+            "(^ (+ (+ x y) z) 1000)"
+        );
+        assert_eq!(expanded("(^ 2 -1000000)")?, "(^ 2 -1000000)");
+        assert_eq!(
+            expanded("(^ (* 2 x) 4294967295)")?,
+            "(^ (* 2 x) 4294967295)"
+        );
+        // however they're nested
+        let big = "1".to_string() + &"0".repeat(1000);
+        assert_eq!(expanded("(^ (^ 10 1000) 1000)")?, format!("(^ {big} 1000)"));
+        assert_eq!(expanded("(^ (^ x 1000) 1000)")?, "(^ (^ x 1000) 1000)");
+        assert_eq!(
+            expanded("(^ (* (^ 10 1000) (+ x 1)) 1000)")?,
+            format!("(^ (+ (* {big} x) {big}) 1000)")
+        );
+        assert_eq!(expanded("(^ (^ 10 2) 3)")?, "1000000");
+        assert_eq!(power_terms(3, 2), 6);
+        assert_eq!(power_terms(2, 999), 1000);
+        let polynomial = Polynomial::from_expr(&"(^ (+ x (+ y 1)) 12)".parse()?);
+        assert_eq!(polynomial.terms().count(), 91);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect() -> Result<()> {
+        let collected = collect(&"(+ (* a x) (+ (* b x) (* x x)))".parse()?, 'x');
+        assert_eq!(collected.to_string(), "(+ (^ x 2) (* (+ a b) x))");
+        let collected = collect(&"(- (* (+ x y) (+ x 1)) (* 3 x))".parse()?, 'x');
+        assert_eq!(collected.to_string(), "(+ (+ (^ x 2) (* (- y 2) x)) y)");
+        let collected = collect(&"(- (sin x) (* 2 (* a x)))".parse()?, 'x');
+        assert_eq!(collected.to_string(), "(+ (- (* (* 2 a) x)) (sin x))");
+
+        Ok(())
+    }
+}
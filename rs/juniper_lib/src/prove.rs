@@ -0,0 +1,96 @@
+use std::fmt::Display;
+
+use egg::{FlatTerm, StopReason, Symbol};
+
+use crate::MathExpression;
+
+// the outcome of trying to prove two expressions equal
+#[derive(Debug, Clone)]
+pub enum Proof {
+    Proved {
+        // how the left hand side was rewritten into the right hand side
+        explanation: String,
+        // the rules used by each step of the explanation, as Lean `rw` steps (see rule_step)
+        steps: Vec<String>,
+    },
+    // saturation stopped without the two sides meeting, which doesn't mean they're different
+    Inconclusive {
+        stop_reason: StopReason,
+        iterations: usize,
+        egraph_nodes: usize,
+        egraph_classes: usize,
+    },
+}
+
+impl Proof {
+    pub fn is_proved(&self) -> bool {
+        matches!(self, Proof::Proved { .. })
+    }
+}
+
+impl Display for Proof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Proof::Proved { steps, .. } if steps.is_empty() => write!(f, "proved (by rfl)"),
+            Proof::Proved { steps, .. } => write!(f, "proved by rw [{}]", steps.join(", ")),
+            Proof::Inconclusive {
+                stop_reason,
+                iterations,
+                egraph_nodes,
+                egraph_classes,
+            } => write!(
+                f,
+                "inconclusive: saturation stopped ({stop_reason:?}) after {iterations} \
+                 iteration(s), with {egraph_nodes} e-nodes in {egraph_classes} e-classes"
+            ),
+        }
+    }
+}
+
+// the name of the rule a step of a flat explanation rewrote by, and whether it was used backwards
+fn step_rule(term: &FlatTerm<MathExpression>) -> Option<(Symbol, bool)> {
+    match (term.forward_rule, term.backward_rule) {
+        (Some(rule), _) => Some((rule, false)),
+        (_, Some(rule)) => Some((rule, true)),
+        _ => term.children.iter().find_map(step_rule),
+    }
+}
+
+// rules converted from theorems are named `<theorem>_forward` and `<theorem>_backward`, so a
+// step is the theorem (reversed with `←` where the rule rewrote right to left). Other rules
+// (such as the REPL's assignments) are left as they are
+fn rule_step(rule: &str, backwards: bool) -> String {
+    let (theorem, reversed) = match rule.rsplit_once('_') {
+        Some((theorem, "forward")) => (theorem, backwards),
+        Some((theorem, "backward")) => (theorem, !backwards),
+        _ => (rule, backwards),
+    };
+    if reversed {
+        format!("← {theorem}")
+    } else {
+        theorem.to_string()
+    }
+}
+
+// the Lean steps of a flat explanation (whose first term is the starting expression)
+pub(crate) fn explanation_steps(explanation: &[FlatTerm<MathExpression>]) -> Vec<String> {
+    explanation
+        .iter()
+        .skip(1)
+        .filter_map(step_rule)
+        .map(|(rule, backwards)| rule_step(rule.as_str(), backwards))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rule_step;
+
+    #[test]
+    fn test_rule_step() {
+        assert_eq!(rule_step("Rat.add_zero_forward", false), "Rat.add_zero");
+        assert_eq!(rule_step("Rat.add_zero_forward", true), "← Rat.add_zero");
+        assert_eq!(rule_step("Rat.add_zero_backward", false), "← Rat.add_zero");
+        assert_eq!(rule_step("assignment_0_f", false), "assignment_0_f");
+    }
+}
@@ -0,0 +1,444 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use egg::{AstSize, CostFunction, Extractor, Id, RecExpr, StopReason};
+
+use crate::extract::{
+    Candidates, Constrained, DagExtractor, Forbidden, Forbidding, KBestExtractor,
+    NoRepresentativeError,
+};
+use crate::linear::{is_linear, solve_linear_system, LinearSolution};
+use crate::prove::{explanation_steps, Proof};
+use crate::solve::{node, subexpr};
+use crate::{get_juniper_rules_lenient, JuniperRewrite, JuniperRunner, MathExpression, NodeCost};
+
+// the outcome of simplifying a single expression
+#[derive(Debug, Clone)]
+pub struct Simplification<Cost> {
+    pub best: RecExpr<MathExpression>,
+    pub cost: Cost,
+    pub stop_reason: StopReason,
+    pub iterations: usize,
+    pub egraph_nodes: usize,
+    pub egraph_classes: usize,
+    // how the input was rewritten into best, if explanations were enabled
+    pub explanation: Option<String>,
+}
+
+// equality saturation over a set of rules followed by extraction of the cheapest equivalent
+// expression, with limits on how long saturation may run for
+#[derive(Clone)]
+pub struct Simplifier<F = fn() -> AstSize> {
+    rules: Vec<JuniperRewrite>,
+    iter_limit: usize,
+    node_limit: usize,
+    time_limit: Duration,
+    // extraction consumes its cost function, so a fresh one is made for every simplification
+    cost_function: F,
+    explanations: bool,
+}
+
+impl Simplifier {
+    // the limits default to egg's
+    pub fn new(rules: Vec<JuniperRewrite>) -> Self {
+        Self {
+            rules,
+            iter_limit: 30,
+            node_limit: 10_000,
+            time_limit: Duration::from_secs(5),
+            cost_function: || AstSize,
+            explanations: false,
+        }
+    }
+
+    // a simplifier over every rule that can be converted from the embedded theorems
+    pub fn embedded() -> Result<Self> {
+        Ok(Self::new(get_juniper_rules_lenient()?.0))
+    }
+}
+
+impl<F: Fn() -> C, C: CostFunction<MathExpression>> Simplifier<F> {
+    pub fn with_iter_limit(mut self, iter_limit: usize) -> Self {
+        self.iter_limit = iter_limit;
+        self
+    }
+
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = node_limit;
+        self
+    }
+
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = time_limit;
+        self
+    }
+
+    pub fn with_cost_function<G: Fn() -> D, D: CostFunction<MathExpression>>(
+        self,
+        cost_function: G,
+    ) -> Simplifier<G> {
+        Simplifier {
+            rules: self.rules,
+            iter_limit: self.iter_limit,
+            node_limit: self.node_limit,
+            time_limit: self.time_limit,
+            cost_function,
+            explanations: self.explanations,
+        }
+    }
+
+    pub fn with_explanations(mut self, explanations: bool) -> Self {
+        self.explanations = explanations;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Vec<JuniperRewrite>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    // only keep the rules whose names satisfy the predicate
+    pub fn retain_rules(mut self, mut predicate: impl FnMut(&str) -> bool) -> Self {
+        self.rules.retain(|rule| predicate(rule.name.as_str()));
+        self
+    }
+
+    pub fn add_rules(&mut self, rules: impl IntoIterator<Item = JuniperRewrite>) {
+        self.rules.extend(rules);
+    }
+
+    pub fn rules(&self) -> &[JuniperRewrite] {
+        &self.rules
+    }
+
+    // a runner with this simplifier's limits (and explanations, if enabled)
+    pub fn runner(&self) -> JuniperRunner {
+        let runner = JuniperRunner::default()
+            .with_iter_limit(self.iter_limit)
+            .with_node_limit(self.node_limit)
+            .with_time_limit(self.time_limit);
+        if self.explanations {
+            runner.with_explanations_enabled()
+        } else {
+            runner
+        }
+    }
+
+    pub fn simplify(&self, expr: &RecExpr<MathExpression>) -> Simplification<C::Cost> {
+        self.simplify_with(expr, (self.cost_function)())
+    }
+
+    // simplify with a one-off cost function instead of this simplifier's own
+    pub fn simplify_with<D: CostFunction<MathExpression>>(
+        &self,
+        expr: &RecExpr<MathExpression>,
+        cost_function: D,
+    ) -> Simplification<D::Cost> {
+        let mut runner = self.runner().with_expr(expr).run(&self.rules);
+        let extractor = Extractor::new(&runner.egraph, cost_function);
+        let (cost, best) = extractor.find_best(runner.roots[0]);
+
+        let explanation = self
+            .explanations
+            .then(|| runner.explain_equivalence(expr, &best).get_flat_string());
+        let report = runner.report();
+
+        Simplification {
+            best,
+            cost,
+            stop_reason: report.stop_reason,
+            iterations: report.iterations,
+            egraph_nodes: report.egraph_nodes,
+            egraph_classes: report.egraph_classes,
+            explanation,
+        }
+    }
+
+    // simplify into the cheapest equivalent expression which avoids the forbidden operators and
+    // variables, failing with a NoRepresentativeError if there isn't one
+    pub fn simplify_avoiding(
+        &self,
+        expr: &RecExpr<MathExpression>,
+        forbidden: &Forbidden,
+    ) -> Result<Simplification<C::Cost>> {
+        self.simplify_avoiding_with(expr, (self.cost_function)(), forbidden)
+    }
+
+    pub fn simplify_avoiding_with<D: CostFunction<MathExpression>>(
+        &self,
+        expr: &RecExpr<MathExpression>,
+        cost_function: D,
+        forbidden: &Forbidden,
+    ) -> Result<Simplification<D::Cost>> {
+        let simplification =
+            self.simplify_with(expr, Forbidding::new(cost_function, forbidden.clone()));
+
+        match simplification.cost {
+            Constrained::Allowed(cost) => Ok(Simplification {
+                best: simplification.best,
+                cost,
+                stop_reason: simplification.stop_reason,
+                iterations: simplification.iterations,
+                egraph_nodes: simplification.egraph_nodes,
+                egraph_classes: simplification.egraph_classes,
+                explanation: simplification.explanation,
+            }),
+            Constrained::Forbidden => Err(NoRepresentativeError {
+                forbidden: forbidden.clone(),
+                stop_reason: simplification.stop_reason,
+            }
+            .into()),
+        }
+    }
+
+    // simplify by DAG cost, which only counts repeated subexpressions once (see DagExtractor)
+    pub fn simplify_dag(&self, expr: &RecExpr<MathExpression>) -> Simplification<f64>
+    where
+        C: NodeCost,
+    {
+        self.simplify_dag_with(expr, (self.cost_function)())
+    }
+
+    pub fn simplify_dag_with(
+        &self,
+        expr: &RecExpr<MathExpression>,
+        node_cost: impl NodeCost,
+    ) -> Simplification<f64> {
+        let mut runner = self.runner().with_expr(expr).run(&self.rules);
+        let (cost, best) = DagExtractor::new(&runner.egraph, node_cost).find_best(runner.roots[0]);
+
+        let explanation = self
+            .explanations
+            .then(|| runner.explain_equivalence(expr, &best).get_flat_string());
+        let report = runner.report();
+
+        Simplification {
+            best,
+            cost,
+            stop_reason: report.stop_reason,
+            iterations: report.iterations,
+            egraph_nodes: report.egraph_nodes,
+            egraph_classes: report.egraph_classes,
+            explanation,
+        }
+    }
+
+    // up to k structurally distinct equivalent expressions, cheapest first
+    pub fn alternatives(&self, expr: &RecExpr<MathExpression>, k: usize) -> Candidates<C::Cost> {
+        self.alternatives_with(expr, (self.cost_function)(), k)
+    }
+
+    pub fn alternatives_with<D: CostFunction<MathExpression>>(
+        &self,
+        expr: &RecExpr<MathExpression>,
+        cost_function: D,
+        k: usize,
+    ) -> Candidates<D::Cost> {
+        let runner = self.runner().with_expr(expr).run(&self.rules);
+        KBestExtractor::new(&runner.egraph, cost_function, k).find_k_best(runner.roots[0])
+    }
+
+    // saturate both sides in one e-graph, stopping as soon as they're equal (in which case the
+    // proof is explained whether or not this simplifier has explanations enabled)
+    pub fn prove_equal(
+        &self,
+        lhs: &RecExpr<MathExpression>,
+        rhs: &RecExpr<MathExpression>,
+    ) -> Proof {
+        let mut runner = self
+            .runner()
+            .with_explanations_enabled()
+            .with_expr(lhs)
+            .with_expr(rhs)
+            .with_hook(|runner| {
+                if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+                    Err("proved".to_string())
+                } else {
+                    Ok(())
+                }
+            })
+            .run(&self.rules);
+
+        if runner.egraph.find(runner.roots[0]) == runner.egraph.find(runner.roots[1]) {
+            let mut explanation = runner.explain_equivalence(lhs, rhs);
+            let steps = explanation_steps(explanation.make_flat_explanation());
+            Proof::Proved {
+                explanation: explanation.get_flat_string(),
+                steps,
+            }
+        } else {
+            let report = runner.report();
+            Proof::Inconclusive {
+                stop_reason: report.stop_reason,
+                iterations: report.iterations,
+                egraph_nodes: report.egraph_nodes,
+                egraph_classes: report.egraph_classes,
+            }
+        }
+    }
+
+    // solve a linear system, simplifying the sides of any equation which isn't already linear in
+    // the unknowns (with rational coefficients) in case that makes it so
+    pub fn solve_linear_system(
+        &self,
+        equations: &[RecExpr<MathExpression>],
+        unknowns: &[char],
+    ) -> Result<LinearSolution> {
+        let equations = equations
+            .iter()
+            .map(|equation| match &equation[equation.root()] {
+                MathExpression::Eq([lhs, rhs]) if !is_linear(equation, unknowns) => {
+                    let lhs = self.simplify(&subexpr(equation, *lhs)).best;
+                    let rhs = self.simplify(&subexpr(equation, *rhs)).best;
+                    node(
+                        MathExpression::Eq([Id::from(0), Id::from(1)]),
+                        &[&lhs, &rhs],
+                    )
+                }
+                _ => equation.clone(),
+            })
+            .collect::<Vec<_>>();
+        solve_linear_system(&equations, unknowns)
+    }
+
+    pub fn simplify_str(&self, expr: &str) -> Result<Simplification<C::Cost>> {
+        Ok(self.simplify(&expr.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use egg::{rewrite, AstDepth, StopReason};
+
+    use super::Simplifier;
+    use crate::{JuniperRewrite, Proof};
+
+    fn rules() -> Vec<JuniperRewrite> {
+        vec![
+            rewrite!("add_zero"; "(+ ?a 0)" => "?a"),
+            rewrite!("add_comm"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+            rewrite!("mul_one"; "(* ?a 1)" => "?a"),
+        ]
+    }
+
+    #[test]
+    fn test_simplify() -> Result<()> {
+        let simplifier = Simplifier::new(rules());
+        let result = simplifier.simplify_str("(+ 0 (* x 1))")?;
+
+        assert_eq!(result.best.to_string(), "x");
+        assert_eq!(result.cost, 1);
+        assert!(matches!(result.stop_reason, StopReason::Saturated));
+        assert!(result.explanation.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplifier_options() -> Result<()> {
+        // without add_comm, `0 + x` is stuck
+        let simplifier = Simplifier::new(rules()).retain_rules(|name| name != "add_comm");
+        assert_eq!(simplifier.rules().len(), 2);
+        assert_eq!(
+            simplifier.simplify_str("(+ 0 x)")?.best.to_string(),
+            "(+ 0 x)"
+        );
+
+        let limited = Simplifier::new(rules())
+            .with_iter_limit(1)
+            .with_time_limit(Duration::from_secs(1));
+        assert!(matches!(
+            limited.simplify_str("(+ 0 (+ 0 (+ 0 x)))")?.stop_reason,
+            StopReason::IterationLimit(1)
+        ));
+
+        let explained = Simplifier::new(rules())
+            .with_cost_function(|| AstDepth)
+            .with_explanations(true)
+            .simplify_str("(+ 0 x)")?;
+        assert_eq!(explained.best.to_string(), "x");
+        assert_eq!(explained.cost, 1);
+        let explanation = explained.explanation.unwrap();
+        assert!(explanation.contains("add_comm") && explanation.contains("add_zero"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alternatives() -> Result<()> {
+        let simplifier = Simplifier::new(rules());
+        let alternatives = simplifier.alternatives(&"(+ 0 (* x 1))".parse()?, 4);
+
+        let forms = alternatives
+            .iter()
+            .map(|(cost, expr)| (*cost, expr.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forms,
+            [
+                (1, "x".to_string()),
+                (3, "(+ 0 x)".to_string()),
+                (3, "(+ x 0)".to_string()),
+                (3, "(* x 1)".to_string()),
+            ]
+        );
+
+        // unless the class is cyclic, there are only so many distinct forms
+        assert_eq!(simplifier.alternatives(&"(+ x y)".parse()?, 10).len(), 2);
+        assert!(simplifier.alternatives(&"x".parse()?, 0).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prove_equal() -> Result<()> {
+        let simplifier = Simplifier::new(vec![
+            rewrite!("Rat.add_zero_forward"; "(+ ?a 0)" => "?a"),
+            rewrite!("Rat.add_comm_forward"; "(+ ?a ?b)" => "(+ ?b ?a)"),
+        ]);
+
+        let proof = simplifier.prove_equal(&"(+ 0 x)".parse()?, &"x".parse()?);
+        assert!(proof.is_proved());
+        assert_eq!(
+            proof.to_string(),
+            "proved by rw [Rat.add_comm, Rat.add_zero]"
+        );
+
+        // there's no rule for multiplication
+        let proof = simplifier.prove_equal(&"(* 1 x)".parse()?, &"x".parse()?);
+        assert!(matches!(
+            proof,
+            Proof::Inconclusive {
+                stop_reason: StopReason::Saturated,
+                ..
+            }
+        ));
+
+        // constant folding isn't a theorem, so it's named as it is
+        let proof = simplifier.prove_equal(&"(+ 1 2)".parse()?, &"3".parse()?);
+        assert_eq!(proof.to_string(), "proved by rw [constant_fold]");
+        let proof = simplifier.prove_equal(&"(+ x y)".parse()?, &"(+ x y)".parse()?);
+        assert_eq!(proof.to_string(), "proved (by rfl)");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_linear_system() -> Result<()> {
+        let simplifier = Simplifier::new(vec![rewrite!("mul_zero"; "(* ?a 0)" => "0")]);
+        let equations = ["(= (+ x (* (* y y) 0)) 1)", "(= (- x y) 3)"]
+            .iter()
+            .map(|equation| equation.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // y * y * 0 isn't linear until it's simplified
+        assert!(crate::solve_linear_system(&equations, &['x', 'y']).is_err());
+        let solution = simplifier.solve_linear_system(&equations, &['x', 'y'])?;
+        assert_eq!(solution.to_string(), "x = 1, y = -2");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,830 @@
+use std::fmt::Display;
+
+use anyhow::{Error, Result};
+use egg::{Id, Language, RecExpr};
+use num::{BigInt, BigRational, Integer, One, Signed, Zero};
+
+use crate::{JuniperBigRational, MathExpression};
+
+pub(crate) type Expr = RecExpr<MathExpression>;
+
+// powers are only worked out (or multiplied out) while the result has at most this degree and
+// coefficients of at most about this many bits, so that something like (^ 2 1000000) or
+// (^ (^ 10 1000) 1000) stays a power instead of exhausting memory
+pub(crate) const MAX_DEGREE: u32 = 1000;
+pub(crate) const MAX_BITS: u64 = 1 << 16;
+
+// whether the n-th power of a polynomial with these coefficients and degree (a constant being a
+// single coefficient of degree 0) is within those bounds: it has n times the degree, and each of
+// its coefficients is a sum of at most terms^n products of n coefficients
+pub(crate) fn small_power<'a>(
+    coefficients: impl IntoIterator<Item = &'a BigRational>,
+    degree: u32,
+    n: &BigInt,
+) -> bool {
+    let Ok(n) = u64::try_from(n.abs()) else {
+        return false;
+    };
+    let (terms, bits) = coefficients
+        .into_iter()
+        .fold((0_u64, 0), |(terms, bits), c| {
+            (terms + 1, bits.max(c.numer().bits() + c.denom().bits()))
+        });
+    let bits = bits + u64::from(u64::BITS - terms.leading_zeros());
+    u64::from(degree).saturating_mul(n) <= u64::from(MAX_DEGREE)
+        && bits.saturating_mul(n) <= MAX_BITS
+}
+
+// something a solution only holds under
+#[derive(Debug, Clone, PartialEq)]
+pub enum SideCondition {
+    NonNegative(Expr),
+    NonZero(Expr),
+}
+
+impl SideCondition {
+    // whether the condition holds, if that can be worked out exactly
+    pub fn decide(&self) -> Option<bool> {
+        match self {
+            SideCondition::NonNegative(expr) => rational(expr).map(|r| !r.is_negative()),
+            SideCondition::NonZero(expr) => rational(expr).map(|r| !r.is_zero()),
+        }
+    }
+}
+
+impl Display for SideCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SideCondition::NonNegative(expr) => write!(f, "{expr} ≥ 0"),
+            SideCondition::NonZero(expr) => write!(f, "{expr} ≠ 0"),
+        }
+    }
+}
+
+// a value of the solved-for variable, which may repeat with a period (for any integer k) and may
+// only hold under some conditions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub value: Expr,
+    pub period: Option<Expr>,
+    pub conditions: Vec<SideCondition>,
+}
+
+impl Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)?;
+        if let Some(period) = &self.period {
+            write!(f, " + {period}k")?;
+        }
+        if !self.conditions.is_empty() {
+            let conditions = self
+                .conditions
+                .iter()
+                .map(SideCondition::to_string)
+                .collect::<Vec<_>>();
+            write!(f, " if {}", conditions.join(" and "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolutionSet {
+    // distinct solutions (possibly none)
+    Finite(Vec<Solution>),
+    // the equation holds whatever the variable is
+    All,
+}
+
+pub(crate) fn constant(r: BigRational) -> Expr {
+    let mut expr = RecExpr::default();
+    expr.add(MathExpression::Constant(JuniperBigRational(r)));
+    expr
+}
+
+fn integer(n: i64) -> BigRational {
+    BigRational::from_integer(n.into())
+}
+
+// a node over the given subexpressions (its own children are ignored)
+pub(crate) fn node(op: MathExpression, children: &[&Expr]) -> Expr {
+    let mut i = 0;
+    let op = op.map_children(|_| {
+        i += 1;
+        Id::from(i - 1)
+    });
+    op.join_recexprs(|id| children[usize::from(id)])
+}
+
+pub(crate) fn subexpr(expr: &Expr, id: Id) -> Expr {
+    expr[id].build_recexpr(|child| expr[child].clone())
+}
+
+fn occurrences(expr: &Expr, id: Id, var: char) -> usize {
+    match &expr[id] {
+        MathExpression::Variable(v) => usize::from(*v == var),
+        node => node
+            .children()
+            .iter()
+            .map(|child| occurrences(expr, *child, var))
+            .sum(),
+    }
+}
+
+// the exact value of an expression with no variables, if it's rational
+pub(crate) fn rational(expr: &Expr) -> Option<BigRational> {
+    fn go(expr: &Expr, id: Id) -> Option<BigRational> {
+        let x = |id: &Id| go(expr, *id);
+        Some(match &expr[id] {
+            MathExpression::Constant(c) => c.0.clone(),
+            MathExpression::Add([a, b]) => x(a)? + x(b)?,
+            MathExpression::Sub([a, b]) => x(a)? - x(b)?,
+            MathExpression::Mul([a, b]) => x(a)? * x(b)?,
+            MathExpression::Div([a, b]) => x(a)? / Some(x(b)?).filter(|b| !b.is_zero())?,
+            MathExpression::Neg(a) => -x(a)?,
+            MathExpression::Inv(a) => Some(x(a)?).filter(|a| !a.is_zero())?.recip(),
+            MathExpression::Pow([a, b]) => {
+                let (base, exponent) = (x(a)?, x(b)?);
+                if !exponent.is_integer()
+                    || !small_power([&base], 0, exponent.numer())
+                    || (base.is_zero() && exponent.is_negative())
+                {
+                    return None;
+                }
+                num::pow::Pow::pow(&base, exponent.numer())
+            }
+            _ => return None,
+        })
+    }
+    go(expr, expr.root())
+}
+
+// the exact n-th root of a rational, if it has one (negative n aren't handled)
+fn exact_root(r: &BigRational, n: u32) -> Option<BigRational> {
+    let root = |i: &BigInt| Some(i.nth_root(n)).filter(|root| root.pow(n) == *i);
+    if r.is_negative() {
+        // only odd roots of negative numbers are real
+        return (n % 2 == 1).then(|| exact_root(&-r, n).map(|root| -root))?;
+    }
+    Some(BigRational::new(root(r.numer())?, root(r.denom())?))
+}
+
+// an expression as a rational multiple of something non-rational, if it isn't rational itself
+fn coefficient(expr: &Expr) -> (BigRational, Option<Expr>) {
+    if let Some(r) = rational(expr) {
+        return (r, None);
+    }
+    match &expr[expr.root()] {
+        MathExpression::Mul([a, b]) => match rational(&subexpr(expr, *a)) {
+            Some(c) => (c, Some(subexpr(expr, *b))),
+            None => (BigRational::one(), Some(expr.clone())),
+        },
+        MathExpression::Neg(a) => {
+            let (c, rest) = coefficient(&subexpr(expr, *a));
+            (-c, rest)
+        }
+        _ => (BigRational::one(), Some(expr.clone())),
+    }
+}
+
+fn scaled(k: BigRational, rest: Option<Expr>) -> Expr {
+    match rest {
+        None => constant(k),
+        Some(_) if k.is_zero() => constant(k),
+        Some(rest) if k.is_one() => rest,
+        Some(rest) if (-&k).is_one() => node(MathExpression::Neg(Id::from(0)), &[&rest]),
+        Some(rest) => node(
+            MathExpression::Mul([Id::from(0); 2]),
+            &[&constant(k), &rest],
+        ),
+    }
+}
+
+// builders which fold constants (and rational multiples) and drop identities, so solutions stay
+// readable
+pub(crate) fn add(a: &Expr, b: &Expr) -> Expr {
+    match (rational(a), rational(b)) {
+        (Some(a), Some(b)) => constant(a + b),
+        (Some(a), _) if a.is_zero() => b.clone(),
+        (_, Some(b)) if b.is_zero() => a.clone(),
+        _ => node(MathExpression::Add([Id::from(0); 2]), &[a, b]),
+    }
+}
+
+pub(crate) fn sub(a: &Expr, b: &Expr) -> Expr {
+    match (rational(a), rational(b)) {
+        (Some(a), Some(b)) => constant(a - b),
+        (Some(a), _) if a.is_zero() => neg(b),
+        (_, Some(b)) if b.is_zero() => a.clone(),
+        _ => node(MathExpression::Sub([Id::from(0); 2]), &[a, b]),
+    }
+}
+
+pub(crate) fn mul(a: &Expr, b: &Expr) -> Expr {
+    match (rational(a), rational(b)) {
+        (Some(a), Some(b)) => constant(a * b),
+        (Some(r), None) => {
+            let (c, rest) = coefficient(b);
+            scaled(r * c, rest)
+        }
+        (None, Some(r)) => {
+            let (c, rest) = coefficient(a);
+            scaled(r * c, rest)
+        }
+        _ => node(MathExpression::Mul([Id::from(0); 2]), &[a, b]),
+    }
+}
+
+fn div(a: &Expr, b: &Expr) -> Expr {
+    match rational(b) {
+        Some(b) if !b.is_zero() => {
+            let (c, rest) = coefficient(a);
+            scaled(c / b, rest)
+        }
+        _ => node(MathExpression::Div([Id::from(0); 2]), &[a, b]),
+    }
+}
+
+pub(crate) fn neg(a: &Expr) -> Expr {
+    let (c, rest) = coefficient(a);
+    scaled(-c, rest)
+}
+
+fn inv(a: &Expr) -> Expr {
+    match rational(a) {
+        Some(a) if !a.is_zero() => constant(a.recip()),
+        _ => node(MathExpression::Inv(Id::from(0)), &[a]),
+    }
+}
+
+pub(crate) fn pow(a: &Expr, n: BigRational) -> Expr {
+    match rational(a) {
+        Some(a)
+            if n.is_integer()
+                && small_power([&a], 0, n.numer())
+                && !(a.is_zero() && n.is_negative()) =>
+        {
+            constant(num::pow::Pow::pow(&a, n.numer()))
+        }
+        _ if n.is_one() => a.clone(),
+        _ => node(MathExpression::Pow([Id::from(0); 2]), &[a, &constant(n)]),
+    }
+}
+
+// the square root of a non-negative rational, as a rational multiple of the square root of an
+// integer without (small) square factors
+fn sqrt(r: &BigRational) -> Expr {
+    // sqrt(n / d) = sqrt(n * d) / d
+    let mut radicand = r.numer() * r.denom();
+    let mut factor = BigInt::one();
+    let mut prime = BigInt::from(2);
+    while prime < BigInt::from(10_000) && &prime * &prime <= radicand {
+        let square = &prime * &prime;
+        while (&radicand % &square).is_zero() {
+            radicand /= &square;
+            factor *= &prime;
+        }
+        prime += 1;
+    }
+
+    let k = BigRational::new(factor, r.denom().clone());
+    if radicand.is_one() {
+        constant(k)
+    } else {
+        let radicand = constant(BigRational::from_integer(radicand));
+        scaled(
+            k,
+            Some(node(MathExpression::Sqrt(Id::from(0)), &[&radicand])),
+        )
+    }
+}
+
+// the real n-th root of an expression
+fn root(a: &Expr, n: &BigRational) -> Expr {
+    let exact = n
+        .to_integer()
+        .try_into()
+        .ok()
+        .filter(|_| n.is_integer())
+        .and_then(|n: u32| exact_root(&rational(a)?, n));
+    match (exact, rational(a)) {
+        (Some(root), _) => constant(root),
+        // the real odd root of a negative number is minus the root of its absolute value, since
+        // fractional powers are only taken of non-negative numbers
+        (None, Some(r)) if r.is_negative() && n.is_integer() && n.numer().is_odd() => {
+            neg(&pow(&constant(-r), n.recip()))
+        }
+        (None, _) => pow(a, n.recip()),
+    }
+}
+
+// the multiples of π which sin or cos takes rational values at, as (value, [multiples of π]) with
+// rationals as (numerator, denominator)
+type AngleTable = &'static [((i64, i64), &'static [(i64, i64)])];
+
+const SIN_VALUES: AngleTable = &[
+    ((0, 1), &[(0, 1), (1, 1)]),
+    ((1, 2), &[(1, 6), (5, 6)]),
+    ((1, 1), &[(1, 2)]),
+    ((-1, 2), &[(-1, 6), (-5, 6)]),
+    ((-1, 1), &[(-1, 2)]),
+];
+const COS_VALUES: AngleTable = &[
+    ((1, 1), &[(0, 1)]),
+    ((1, 2), &[(1, 3), (-1, 3)]),
+    ((0, 1), &[(1, 2), (-1, 2)]),
+    ((-1, 2), &[(2, 3), (-2, 3)]),
+    ((-1, 1), &[(1, 1)]),
+];
+
+fn pi_multiple(multiple: BigRational) -> Expr {
+    let mut pi = RecExpr::default();
+    pi.add(MathExpression::Pi);
+    if multiple.is_zero() {
+        constant(multiple)
+    } else {
+        mul(&constant(multiple), &pi)
+    }
+}
+
+// an equation `lhs = rhs` where the variable occurs once, in lhs
+struct Branch {
+    lhs: Expr,
+    rhs: Expr,
+    period: Option<Expr>,
+    conditions: Vec<SideCondition>,
+}
+
+impl Branch {
+    fn with(&self, lhs: Expr, rhs: Expr) -> Self {
+        Self {
+            lhs,
+            rhs,
+            period: self.period.clone(),
+            conditions: self.conditions.clone(),
+        }
+    }
+
+    fn condition(mut self, condition: SideCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    // scale the period, if there is one, by the (absolute value of the) given factor
+    fn scale_period(mut self, factor: &Expr, divide: bool) -> Self {
+        let factor = match rational(factor) {
+            Some(factor) => constant(factor.abs()),
+            None => factor.clone(),
+        };
+        self.period = self.period.map(|period| {
+            if divide {
+                div(&period, &factor)
+            } else {
+                mul(&period, &factor)
+            }
+        });
+        self
+    }
+
+    // the solution, unless one of its conditions is known not to hold
+    fn finish(self) -> Option<Solution> {
+        let mut conditions = Vec::new();
+        for condition in self.conditions {
+            match condition.decide() {
+                Some(true) => {}
+                Some(false) => return None,
+                None if conditions.contains(&condition) => {}
+                None => conditions.push(condition),
+            }
+        }
+        Some(Solution {
+            value: self.rhs,
+            period: self.period,
+            conditions,
+        })
+    }
+
+    // the variable has dropped out of lhs, which is 0 whatever it is: every value is a solution if
+    // rhs is 0 too, and none are otherwise
+    fn vanish(self, var: char, what: &str) -> Result<SolutionSet> {
+        let Some(rhs) = rational(&self.rhs) else {
+            return Err(Error::msg(format!(
+                "{var} vanishes from {what}, so the solutions depend on whether {} = 0",
+                self.rhs
+            )));
+        };
+        if !rhs.is_zero() {
+            return Ok(SolutionSet::Finite(Vec::new()));
+        }
+        match self.finish() {
+            None => Ok(SolutionSet::Finite(Vec::new())),
+            Some(solution) if solution.conditions.is_empty() => Ok(SolutionSet::All),
+            Some(solution) => Err(Error::msg(format!(
+                "{var} vanishes from {what}, so every value is a solution only if {}",
+                solution
+                    .conditions
+                    .iter()
+                    .map(SideCondition::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            ))),
+        }
+    }
+}
+
+// undo the operations around the variable one at a time, branching where an inverse has more
+// than one value
+fn isolate(branch: Branch, var: char) -> Result<SolutionSet> {
+    let node = branch.lhs[branch.lhs.root()].clone();
+    let rhs = &branch.rhs;
+
+    let periodic = |what: &str| {
+        Err(Error::msg(format!(
+            "can't invert {what} of a periodic family of solutions"
+        )))
+    };
+
+    let branches = match node {
+        MathExpression::Variable(_) => {
+            return Ok(SolutionSet::Finite(branch.finish().into_iter().collect()))
+        }
+        MathExpression::Add([a, b]) => {
+            let (a, b) = (subexpr(&branch.lhs, a), subexpr(&branch.lhs, b));
+            if occurrences(&a, a.root(), var) > 0 {
+                vec![branch.with(a, sub(rhs, &b))]
+            } else {
+                vec![branch.with(b, sub(rhs, &a))]
+            }
+        }
+        MathExpression::Sub([a, b]) => {
+            let (a, b) = (subexpr(&branch.lhs, a), subexpr(&branch.lhs, b));
+            if occurrences(&a, a.root(), var) > 0 {
+                vec![branch.with(a, add(rhs, &b))]
+            } else {
+                vec![branch.with(b, sub(&a, rhs))]
+            }
+        }
+        MathExpression::Mul([a, b]) => {
+            let (a, b) = (subexpr(&branch.lhs, a), subexpr(&branch.lhs, b));
+            let (inner, factor) = if occurrences(&a, a.root(), var) > 0 {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            if rational(&factor).is_some_and(|factor| factor.is_zero()) {
+                return branch.vanish(var, "a product with 0");
+            }
+            vec![branch
+                .with(inner, div(rhs, &factor))
+                .condition(SideCondition::NonZero(factor.clone()))
+                .scale_period(&factor, true)]
+        }
+        MathExpression::Div([a, b]) => {
+            let (a, b) = (subexpr(&branch.lhs, a), subexpr(&branch.lhs, b));
+            if occurrences(&a, a.root(), var) > 0 {
+                vec![branch
+                    .with(a, mul(rhs, &b))
+                    .condition(SideCondition::NonZero(b.clone()))
+                    .scale_period(&b, false)]
+            } else if branch.period.is_some() {
+                return periodic("a quotient");
+            } else {
+                vec![branch
+                    .with(b, div(&a, rhs))
+                    .condition(SideCondition::NonZero(rhs.clone()))]
+            }
+        }
+        MathExpression::Neg(a) => vec![branch.with(subexpr(&branch.lhs, a), neg(rhs))],
+        _ if branch.period.is_some() => return periodic(&branch.lhs.to_string()),
+        MathExpression::Inv(a) => vec![branch
+            .with(subexpr(&branch.lhs, a), inv(rhs))
+            .condition(SideCondition::NonZero(rhs.clone()))],
+        MathExpression::Sqrt(a) => vec![branch
+            .with(subexpr(&branch.lhs, a), pow(rhs, integer(2)))
+            .condition(SideCondition::NonNegative(rhs.clone()))],
+        MathExpression::Pow([a, b]) => {
+            let (a, b) = (subexpr(&branch.lhs, a), subexpr(&branch.lhs, b));
+            if occurrences(&a, a.root(), var) == 0 {
+                return Err(Error::msg(
+                    "solving for an exponent needs a logarithm, which MathExpression doesn't have",
+                ));
+            }
+            let n = rational(&b)
+                .ok_or_else(|| Error::msg(format!("the exponent {b} isn't a rational constant")))?;
+            if n.is_zero() {
+                return Err(Error::msg(format!("{var} vanishes from a zeroth power")));
+            }
+
+            let even = n.is_integer() && n.numer().is_even();
+            let odd = n.is_integer() && !even;
+            let solved = branch.with(a.clone(), root(rhs, &n.abs()));
+            // odd powers are invertible everywhere, but even and fractional powers are only taken
+            // of (and give) non-negative numbers, and negative powers are never 0. A symbolic odd
+            // root is written as a fractional power too, so it needs a non-negative rhs
+            let solved = if odd && rational(rhs).is_some() {
+                solved
+            } else {
+                solved.condition(SideCondition::NonNegative(rhs.clone()))
+            };
+            let solved = if n.is_negative() {
+                Branch {
+                    rhs: inv(&solved.rhs),
+                    ..solved
+                }
+                .condition(SideCondition::NonZero(rhs.clone()))
+            } else {
+                solved
+            };
+
+            if even {
+                let negative = solved.with(a, neg(&solved.rhs));
+                vec![solved, negative]
+            } else {
+                vec![solved]
+            }
+        }
+        MathExpression::Sin(a) | MathExpression::Cos(a) => {
+            let (table, name) = match node {
+                MathExpression::Sin(_) => (SIN_VALUES, "sin"),
+                _ => (COS_VALUES, "cos"),
+            };
+            let value = rational(rhs).ok_or_else(|| {
+                Error::msg(format!(
+                    "{name} can only be inverted at constants, not {rhs}"
+                ))
+            })?;
+            if value.abs() > BigRational::one() {
+                return Ok(SolutionSet::Finite(Vec::new()));
+            }
+            let (_, multiples) = table
+                .iter()
+                .find(|((n, d), _)| BigRational::new((*n).into(), (*d).into()) == value)
+                .ok_or_else(|| {
+                    Error::msg(format!("{name} has no known exact inverse at {value}"))
+                })?;
+
+            let a = subexpr(&branch.lhs, a);
+            multiples
+                .iter()
+                .map(|(n, d)| Branch {
+                    period: Some(pi_multiple(integer(2))),
+                    ..branch.with(
+                        a.clone(),
+                        pi_multiple(BigRational::new((*n).into(), (*d).into())),
+                    )
+                })
+                .collect()
+        }
+        _ => return Err(Error::msg(format!("can't isolate {var} in {}", branch.lhs))),
+    };
+
+    let mut solutions = Vec::new();
+    for branch in branches {
+        match isolate(branch, var)? {
+            SolutionSet::Finite(branch_solutions) => solutions.extend(branch_solutions),
+            SolutionSet::All => return Ok(SolutionSet::All),
+        }
+    }
+    Ok(SolutionSet::Finite(solutions))
+}
+
+// the coefficients (lowest degree first) of an expression which is a polynomial in the variable
+// with rational coefficients
+fn univariate(expr: &Expr, id: Id, var: char) -> Option<Vec<BigRational>> {
+    fn combine(
+        a: Vec<BigRational>,
+        b: Vec<BigRational>,
+        f: impl Fn(BigRational, BigRational) -> BigRational,
+    ) -> Vec<BigRational> {
+        let zero = || BigRational::zero();
+        (0..a.len().max(b.len()))
+            .map(|i| {
+                f(
+                    a.get(i).cloned().unwrap_or_else(zero),
+                    b.get(i).cloned().unwrap_or_else(zero),
+                )
+            })
+            .collect()
+    }
+
+    fn product(a: &[BigRational], b: &[BigRational]) -> Vec<BigRational> {
+        let mut result = vec![BigRational::zero(); a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                result[i + j] += x * y;
+            }
+        }
+        result
+    }
+
+    let x = |id: &Id| univariate(expr, *id, var);
+    let constant = |id: &Id| rational(&subexpr(expr, *id));
+    Some(match &expr[id] {
+        MathExpression::Constant(c) => vec![c.0.clone()],
+        MathExpression::Variable(v) if *v == var => vec![BigRational::zero(), BigRational::one()],
+        MathExpression::Add([a, b]) => combine(x(a)?, x(b)?, |a, b| a + b),
+        MathExpression::Sub([a, b]) => combine(x(a)?, x(b)?, |a, b| a - b),
+        MathExpression::Mul([a, b]) => product(&x(a)?, &x(b)?),
+        MathExpression::Neg(a) => x(a)?.into_iter().map(|c| -c).collect(),
+        MathExpression::Div([a, b]) => {
+            let divisor = constant(b).filter(|b| !b.is_zero())?;
+            x(a)?.into_iter().map(|c| c / &divisor).collect()
+        }
+        MathExpression::Inv(a) => vec![constant(a).filter(|a| !a.is_zero())?.recip()],
+        MathExpression::Pow([a, b]) => {
+            let exponent = constant(b).filter(|b| b.is_integer() && !b.is_negative())?;
+            let base = x(a)?;
+            let degree = u32::try_from(base.len() - 1).ok()?;
+            if !small_power(&base, degree, exponent.numer()) {
+                return None;
+            }
+            let exponent: u32 = exponent.to_integer().try_into().ok()?;
+            (0..exponent).fold(vec![BigRational::one()], |power, _| product(&power, &base))
+        }
+        _ => return None,
+    })
+}
+
+fn solve_polynomial(mut coefficients: Vec<BigRational>, var: char) -> Result<SolutionSet> {
+    while coefficients.last().is_some_and(Zero::is_zero) {
+        coefficients.pop();
+    }
+    let solution = |value| Solution {
+        value,
+        period: None,
+        conditions: Vec::new(),
+    };
+
+    Ok(match coefficients.as_slice() {
+        [] => SolutionSet::All,
+        [_] => SolutionSet::Finite(Vec::new()),
+        [b, a] => SolutionSet::Finite(vec![solution(constant(-b / a))]),
+        [c, b, a] => {
+            let discriminant = b * b - integer(4) * a * c;
+            let vertex = -b / (integer(2) * a);
+            let offset = div(&sqrt(&discriminant), &constant((integer(2) * a).abs()));
+            SolutionSet::Finite(if discriminant.is_negative() {
+                Vec::new()
+            } else if discriminant.is_zero() {
+                vec![solution(constant(vertex))]
+            } else {
+                vec![
+                    solution(sub(&constant(vertex.clone()), &offset)),
+                    solution(add(&constant(vertex), &offset)),
+                ]
+            })
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "can't solve a polynomial of degree {} in {var}",
+                coefficients.len() - 1
+            )))
+        }
+    })
+}
+
+// solve an equation (an Eq expression) for a variable: exactly if it's a linear or quadratic
+// polynomial in it with rational coefficients, and otherwise by isolating it (which needs it to
+// occur only once) through inverse functions, with the side conditions that needs
+pub fn solve(equation: &Expr, var: char) -> Result<SolutionSet> {
+    let MathExpression::Eq([lhs, rhs]) = equation[equation.root()] else {
+        return Err(Error::msg(format!("{equation} isn't an equation")));
+    };
+    let (lhs, rhs) = (subexpr(equation, lhs), subexpr(equation, rhs));
+
+    match (
+        occurrences(&lhs, lhs.root(), var),
+        occurrences(&rhs, rhs.root(), var),
+    ) {
+        (0, 0) => Err(Error::msg(format!("{var} doesn't occur in {equation}"))),
+        (1, 0) | (0, 1) => {
+            let (lhs, rhs) = if occurrences(&lhs, lhs.root(), var) == 1 {
+                (lhs, rhs)
+            } else {
+                (rhs, lhs)
+            };
+            let isolated = isolate(
+                Branch {
+                    lhs,
+                    rhs,
+                    period: None,
+                    conditions: Vec::new(),
+                },
+                var,
+            )?;
+            let SolutionSet::Finite(isolated) = isolated else {
+                return Ok(SolutionSet::All);
+            };
+            let mut solutions: Vec<Solution> = Vec::new();
+            for solution in isolated {
+                if !solutions.contains(&solution) {
+                    solutions.push(solution);
+                }
+            }
+            Ok(SolutionSet::Finite(solutions))
+        }
+        _ => {
+            let polynomial =
+                univariate(&lhs, lhs.root(), var).zip(univariate(&rhs, rhs.root(), var));
+            let Some((lhs, rhs)) = polynomial else {
+                return Err(Error::msg(format!(
+                    "{var} occurs more than once and {equation} isn't a polynomial in it with \
+                     rational coefficients"
+                )));
+            };
+            let difference = (0..lhs.len().max(rhs.len()))
+                .map(|i| {
+                    lhs.get(i).cloned().unwrap_or_default()
+                        - rhs.get(i).cloned().unwrap_or_default()
+                })
+                .collect();
+            solve_polynomial(difference, var)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{solve, SolutionSet};
+
+    fn solutions(equation: &str, var: char) -> Result<Vec<String>> {
+        match solve(&equation.parse()?, var)? {
+            SolutionSet::Finite(solutions) => {
+                Ok(solutions.iter().map(ToString::to_string).collect())
+            }
+            SolutionSet::All => Ok(vec!["all".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_solve_polynomials() -> Result<()> {
+        assert_eq!(solutions("(= (+ (* 2 x) 3) 7)", 'x')?, ["2"]);
+        assert_eq!(solutions("(= (* x x) (- (* 5 x) 6))", 'x')?, ["2", "3"]);
+        assert_eq!(solutions("(= (^ (- x 1) 2) 0)", 'x')?, ["1"]);
+        assert_eq!(
+            solutions("(= (* x x) 2)", 'x')?,
+            ["(- (sqrt 2))", "(sqrt 2)"]
+        );
+        assert!(solutions("(= (+ (* x x) 1) 0)", 'x')?.is_empty());
+        assert_eq!(
+            solutions("(= (- (* x x) (* 2 x)) 1)", 'x')?,
+            ["(- 1 (sqrt 2))", "(+ 1 (sqrt 2))"]
+        );
+        assert_eq!(solutions("(= (+ x x) (* 2 x))", 'x')?, ["all"]);
+        assert!(solve(&"(= (* x (* x x)) 1)".parse()?, 'x').is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_by_isolation() -> Result<()> {
+        assert_eq!(
+            solutions("(= (sqrt (+ x 1)) y)", 'x')?,
+            ["(- (^ y 2) 1) if y ≥ 0"]
+        );
+        assert_eq!(solutions("(= (* a x) b)", 'x')?, ["(/ b a) if a ≠ 0"]);
+        assert_eq!(solutions("(= (sqrt x) -1)", 'x')?, Vec::<String>::new());
+        assert_eq!(
+            solutions("(= (^ x 2) y)", 'x')?,
+            ["(^ y 1/2) if y ≥ 0", "(- (^ y 1/2)) if y ≥ 0"]
+        );
+        assert_eq!(
+            solutions("(= (sin (* 2 x)) 1/2)", 'x')?,
+            ["(* 1/12 π) + πk", "(* 5/12 π) + πk"]
+        );
+        assert_eq!(solutions("(= (cos x) 2)", 'x')?, Vec::<String>::new());
+        assert_eq!(solutions("(= (inv (^ x 3)) 8)", 'x')?, ["1/2"]);
+        // symbolic odd roots are fractional powers, which only hold for non-negative numbers
+        assert_eq!(solutions("(= (^ x 3) y)", 'x')?, ["(^ y 1/3) if y ≥ 0"]);
+        assert_eq!(solutions("(= (^ x 3) -8)", 'x')?, ["-2"]);
+        assert_eq!(solutions("(= (^ x 3) -2)", 'x')?, ["(- (^ 2 1/3))"]);
+
+        // x drops out of a product with 0
+        assert_eq!(solutions("(= (* 0 x) 0)", 'x')?, ["all"]);
+        assert_eq!(solutions("(= (+ (* x 0) 1) 1)", 'x')?, ["all"]);
+        assert!(solutions("(= (* 0 x) 1)", 'x')?.is_empty());
+        assert!(solve(&"(= (* 0 x) y)".parse()?, 'x').is_err());
+        assert!(solve(&"(= (sin x) 1/3)".parse()?, 'x').is_err());
+        assert!(solve(&"(= (^ 2 x) 8)".parse()?, 'x').is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_big_powers() -> Result<()> {
+        // powers too big to work out (here with 10^6 digits, or of degree 10^6) are left as they
+        // are, however they're nested
+        assert_eq!(
+            solutions("(= (+ x 1) (^ (^ 10 1000) 1000))", 'x')?,
+            ["(- (^ (^ 10 1000) 1000) 1)"]
+        );
+        assert_eq!(solutions("(= (^ (^ (+ x 1) 1000) 1000) 0)", 'x')?, ["-1"]);
+        // while smaller ones still are
+        assert_eq!(solutions("(= (+ x 1) (^ (^ 10 2) 3))", 'x')?, ["999999"]);
+        assert_eq!(solutions("(= (^ (^ (+ x 1) 2) 2) 0)", 'x')?, ["-1"]);
+
+        Ok(())
+    }
+}
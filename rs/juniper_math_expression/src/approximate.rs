@@ -1,32 +1,45 @@
+use std::collections::HashMap;
+
 use egg::{Id, RecExpr};
 use num::{traits::Inv, ToPrimitive};
 
 use crate::{JuniperBigRational, MathExpression};
 
 pub fn approximate(re: &RecExpr<MathExpression>, id: &Id) -> Option<f64> {
+    approximate_with(re, id, &HashMap::new())
+}
+
+// approximate with the given values for variables (any others can't be approximated)
+pub fn approximate_with(
+    re: &RecExpr<MathExpression>,
+    id: &Id,
+    variables: &HashMap<char, f64>,
+) -> Option<f64> {
+    let approximate = |id| approximate_with(re, id, variables);
     match &re[*id] {
         MathExpression::Constant(JuniperBigRational(big_rat)) => big_rat.to_f64(),
-        MathExpression::Variable(_) => None,
+        MathExpression::Variable(v) => variables.get(v).copied(),
         MathExpression::Pi => Some(std::f64::consts::PI),
         MathExpression::Assign(_) => None,
         MathExpression::Eq(_) => None, // maybe in the future?
-        MathExpression::Add([a, b]) => Some(approximate(re, &a)? + approximate(re, &b)?),
-        MathExpression::Sub([a, b]) => Some(approximate(re, &a)? - approximate(re, &b)?),
-        MathExpression::Mul([a, b]) => Some(approximate(re, &a)? * approximate(re, &b)?),
-        MathExpression::Div([a, b]) => Some(approximate(re, &a)? / approximate(re, &b)?),
-        MathExpression::Pow([a, b]) => Some(approximate(re, &a)?.powf(approximate(re, &b)?)),
-        MathExpression::Sqrt(n) => Some(approximate(re, &n)?.sqrt()),
-        MathExpression::Neg(n) => Some(-approximate(re, &n)?),
+        MathExpression::Add([a, b]) => Some(approximate(a)? + approximate(b)?),
+        MathExpression::Sub([a, b]) => Some(approximate(a)? - approximate(b)?),
+        MathExpression::Mul([a, b]) => Some(approximate(a)? * approximate(b)?),
+        MathExpression::Div([a, b]) => Some(approximate(a)? / approximate(b)?),
+        MathExpression::Pow([a, b]) => Some(approximate(a)?.powf(approximate(b)?)),
+        MathExpression::Sqrt(n) => Some(approximate(n)?.sqrt()),
+        MathExpression::Neg(n) => Some(-approximate(n)?),
         MathExpression::Inv(n) => Some({
-            let approximation = approximate(re, &n)?;
+            let approximation = approximate(n)?;
             if approximation == 0.0 {
                 0.0
             } else {
                 approximation.inv()
             }
         }),
-        MathExpression::Sin(n) => Some(approximate(re, &n)?.sin()),
-        MathExpression::Cos(n) => Some(approximate(re, &n)?.cos()),
+        MathExpression::Sin(n) => Some(approximate(n)?.sin()),
+        MathExpression::Cos(n) => Some(approximate(n)?.cos()),
+        MathExpression::Cast(n) => approximate(n),
         MathExpression::Antiderivative(_) => None,
         MathExpression::Derivative(_) => None,
         MathExpression::Integral(_) => None,
@@ -35,11 +48,11 @@ pub fn approximate(re: &RecExpr<MathExpression>, id: &Id) -> Option<f64> {
 
 #[cfg(test)]
 mod tests {
-    use std::f64::EPSILON;
-
     use anyhow::Result;
 
-    use super::approximate;
+    use std::collections::HashMap;
+
+    use super::{approximate, approximate_with};
 
     #[test]
     fn test_approx_none() -> Result<()> {
@@ -62,6 +75,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::float_equality_without_abs)]
     fn test_approx_some() -> Result<()> {
         let me_constant = "5.867".parse()?;
         let approximation_constant = approximate(&me_constant, &me_constant.root());
@@ -69,74 +83,88 @@ mod tests {
 
         assert!(
             approximation_constant.expect("approximation_constant failed") - actual_constant
-                < EPSILON
+                < f64::EPSILON
         );
 
         let me_pi = "π".parse()?;
         let approximation_pi = approximate(&me_pi, &me_pi.root());
         let actual_pi = std::f64::consts::PI;
 
-        assert!(approximation_pi.expect("approximation_pi failed") - actual_pi < EPSILON);
+        assert!(approximation_pi.expect("approximation_pi failed") - actual_pi < f64::EPSILON);
 
         let me_add = "(+ 5 5)".parse()?;
         let approximation_add = approximate(&me_add, &me_add.root());
         let actual_add = 10.0;
 
-        assert!(approximation_add.expect("approximation_add failed") - actual_add < EPSILON);
+        assert!(approximation_add.expect("approximation_add failed") - actual_add < f64::EPSILON);
 
         let me_sub = "(- 8 2)".parse()?;
         let approximation_sub = approximate(&me_sub, &me_sub.root());
         let actual_sub = 6.0;
 
-        assert!(approximation_sub.expect("approximation_sub failed") - actual_sub < EPSILON);
+        assert!(approximation_sub.expect("approximation_sub failed") - actual_sub < f64::EPSILON);
 
         let me_mul = "(* 3 2)".parse()?;
         let approximation_mul = approximate(&me_mul, &me_mul.root());
         let actual_mul = 6.0;
 
-        assert!(approximation_mul.expect("approximation_mul failed") - actual_mul < EPSILON);
+        assert!(approximation_mul.expect("approximation_mul failed") - actual_mul < f64::EPSILON);
 
         let me_div = "(/ 3 2)".parse()?;
         let approximation_div = approximate(&me_div, &me_div.root());
         let actual_div = 1.5;
 
-        assert!(approximation_div.expect("approximation_div failed") - actual_div < EPSILON);
+        assert!(approximation_div.expect("approximation_div failed") - actual_div < f64::EPSILON);
 
         let me_pow = "(^ 3 2)".parse()?;
         let approximation_pow = approximate(&me_pow, &me_pow.root());
         let actual_pow = 9.0;
 
-        assert!(approximation_pow.expect("approximation_pow failed") - actual_pow < EPSILON);
+        assert!(approximation_pow.expect("approximation_pow failed") - actual_pow < f64::EPSILON);
 
         let me_sqrt = "(sqrt 17)".parse()?;
         let approximation_sqrt = approximate(&me_sqrt, &me_sqrt.root());
         let actual_sqrt = 4.12310562562;
 
-        assert!(approximation_sqrt.expect("approximation_sqrt failed") - actual_sqrt < EPSILON);
+        assert!(approximation_sqrt.expect("approximation_sqrt failed") - actual_sqrt < f64::EPSILON);
 
         let me_neg = "(- 16)".parse()?;
         let approximation_neg = approximate(&me_neg, &me_neg.root());
         let actual_neg = -16.0;
 
-        assert!(approximation_neg.expect("approximation_neg failed") - actual_neg < EPSILON);
+        assert!(approximation_neg.expect("approximation_neg failed") - actual_neg < f64::EPSILON);
 
         let me_inv = "(inv 4)".parse()?;
         let approximation_inv = approximate(&me_inv, &me_inv.root());
         let actual_inv = 0.25;
 
-        assert!(approximation_inv.expect("approximation_inv failed") - actual_inv < EPSILON);
+        assert!(approximation_inv.expect("approximation_inv failed") - actual_inv < f64::EPSILON);
 
         let me_sin = "(sin 3)".parse()?;
         let approximation_sin = approximate(&me_sin, &me_sin.root());
         let actual_sin = 0.14112000806;
 
-        assert!(approximation_sin.expect("approximation_sin failed") - actual_sin < EPSILON);
+        assert!(approximation_sin.expect("approximation_sin failed") - actual_sin < f64::EPSILON);
 
         let me_cos = "(cos 3)".parse()?;
         let approximation_cos = approximate(&me_cos, &me_cos.root());
         let actual_cos = -0.9899924966;
 
-        assert!(approximation_cos.expect("approximation_cos failed") - actual_cos < EPSILON);
+        assert!(approximation_cos.expect("approximation_cos failed") - actual_cos < f64::EPSILON);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_with() -> Result<()> {
+        let me = "(+ (* x x) y)".parse()?;
+        let variables = HashMap::from([('x', 3.0), ('y', 0.5)]);
+
+        assert_eq!(approximate_with(&me, &me.root(), &variables), Some(9.5));
+        assert_eq!(
+            approximate_with(&me, &me.root(), &HashMap::from([('x', 3.0)])),
+            None
+        );
 
         Ok(())
     }
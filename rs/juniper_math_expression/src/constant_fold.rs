@@ -4,19 +4,25 @@ use num::{
     BigInt, BigRational, FromPrimitive,
 };
 
-use crate::{JuniperBigRational, MathExpression};
+use crate::{JuniperBigRational, MathExpression, Sort};
 
 #[derive(Default, Clone)]
 pub struct ConstantFold;
 
-impl Analysis<MathExpression> for ConstantFold {
-    type Data = Option<(JuniperBigRational, PatternAst<MathExpression>)>;
+#[derive(Debug, Clone)]
+pub struct ConstantFoldData {
+    // the folded value of the e-class (and the constant expression it came from), if any
+    pub constant: Option<(JuniperBigRational, PatternAst<MathExpression>)>,
+    // the smallest known sort containing the value of the e-class
+    pub sort: Sort,
+}
 
-    fn make(
+impl ConstantFold {
+    fn fold(
         egraph: &mut EGraph<MathExpression, ConstantFold>,
         enode: &MathExpression,
-    ) -> Self::Data {
-        let x = |i: &Id| egraph[*i].data.as_ref().map(|d| d.0.clone());
+    ) -> Option<(JuniperBigRational, PatternAst<MathExpression>)> {
+        let x = |i: &Id| egraph[*i].data.constant.as_ref().map(|d| d.0.clone());
         Some(match enode {
             MathExpression::Constant(c) => (c.clone(), format!("{}", c).parse().unwrap()),
             MathExpression::Add([a, b]) => (
@@ -59,16 +65,36 @@ impl Analysis<MathExpression> for ConstantFold {
             _ => return None,
         })
     }
+}
+
+impl Analysis<MathExpression> for ConstantFold {
+    type Data = ConstantFoldData;
+
+    fn make(
+        egraph: &mut EGraph<MathExpression, ConstantFold>,
+        enode: &MathExpression,
+    ) -> Self::Data {
+        ConstantFoldData {
+            constant: Self::fold(egraph, enode),
+            sort: Sort::of_node(enode, |i| egraph[*i].data.sort),
+        }
+    }
 
     fn merge(&mut self, to: &mut Self::Data, from: Self::Data) -> DidMerge {
-        merge_option(to, from, |a, b| {
+        let constant_merge = merge_option(&mut to.constant, from.constant, |a, b| {
             assert_eq!(a.0, b.0, "Merged non-equal constants");
             DidMerge(false, false)
-        })
+        });
+
+        // equal values share a sort, so the merged class gets the tighter of the two
+        let sort_merge = DidMerge(from.sort < to.sort, to.sort < from.sort);
+        to.sort = to.sort.min(from.sort);
+
+        constant_merge | sort_merge
     }
 
     fn modify(egraph: &mut EGraph<MathExpression, ConstantFold>, id: Id) {
-        let data = egraph[id].data.clone();
+        let data = egraph[id].data.constant.clone();
         if let Some((c, pat)) = data {
             if egraph.are_explanations_enabled() {
                 egraph.union_instantiations(
@@ -184,7 +184,7 @@ mod tests {
 
         let from_string_1e10: JuniperBigRational = "1e10".parse()?;
         let manual_1e10 =
-            JuniperBigRational(BigRational::from_integer((10000000000 as u128).into()));
+            JuniperBigRational(BigRational::from_integer(10000000000_u128.into()));
 
         assert_eq!(from_string_1e10, manual_1e10);
 
@@ -195,7 +195,7 @@ mod tests {
 
         let from_string_10e10: JuniperBigRational = "10e10".parse()?;
         let manual_10e10 =
-            JuniperBigRational(BigRational::from_integer((100000000000 as u128).into()));
+            JuniperBigRational(BigRational::from_integer(100000000000_u128.into()));
 
         assert_eq!(from_string_10e10, manual_10e10);
 
@@ -256,7 +256,7 @@ mod tests {
 
         let from_string_0_2348923985e5: JuniperBigRational = "0.2348923985e5".parse()?;
         let manual_0_2348923985e5 =
-            JuniperBigRational(BigRational::new((2348923985 as u32).into(), 100000.into()));
+            JuniperBigRational(BigRational::new(2348923985_u32.into(), 100000.into()));
 
         assert_eq!(from_string_0_2348923985e5, manual_0_2348923985e5);
 
@@ -5,7 +5,10 @@ mod math_expression;
 pub use math_expression::MathExpression;
 
 mod constant_fold;
-pub use constant_fold::ConstantFold;
+pub use constant_fold::{ConstantFold, ConstantFoldData};
+
+mod sort;
+pub use sort::Sort;
 
 mod approximate;
-pub use approximate::approximate;
+pub use approximate::{approximate, approximate_with};
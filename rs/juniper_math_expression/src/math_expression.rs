@@ -19,6 +19,8 @@ define_language! {
         "inv" = Inv(Id),
         "sin" = Sin(Id),
         "cos" = Cos(Id),
+        // a coercion (↑n) from a smaller sort, which doesn't change its argument's value
+        "cast" = Cast(Id),
         "anti-d" = Antiderivative([Id; 2]),
         "d" = Derivative([Id; 2]),
         "int" = Integral([Id; 4]),
@@ -58,7 +60,7 @@ mod tests {
         let mut manual_3259872938572490806830928172794675 = RecExpr::default();
         manual_3259872938572490806830928172794675.add(MathExpression::Constant(
             JuniperBigRational(BigRational::new(
-                (3259872938572490806830928172794675 as u128).into(),
+                3259872938572490806830928172794675_u128.into(),
                 1.into(),
             )),
         ));
@@ -139,7 +141,7 @@ mod tests {
         let from_string_1e10: RecExpr<MathExpression> = "1e10".parse()?;
         let mut manual_1e10 = RecExpr::default();
         manual_1e10.add(MathExpression::Constant(JuniperBigRational(
-            BigRational::from_integer((10000000000 as u128).into()),
+            BigRational::from_integer(10000000000_u128.into()),
         )));
 
         assert_eq!(from_string_1e10, manual_1e10);
@@ -155,7 +157,7 @@ mod tests {
         let from_string_10e10: RecExpr<MathExpression> = "10e10".parse()?;
         let mut manual_10e10 = RecExpr::default();
         manual_10e10.add(MathExpression::Constant(JuniperBigRational(
-            BigRational::from_integer((100000000000 as u128).into()),
+            BigRational::from_integer(100000000000_u128.into()),
         )));
 
         assert_eq!(from_string_10e10, manual_10e10);
@@ -237,7 +239,7 @@ mod tests {
         let from_string_0_2348923985e5: RecExpr<MathExpression> = "0.2348923985e5".parse()?;
         let mut manual_0_2348923985e5 = RecExpr::default();
         manual_0_2348923985e5.add(MathExpression::Constant(JuniperBigRational(
-            BigRational::new((2348923985 as u32).into(), 100000.into()),
+            BigRational::new(2348923985_u32.into(), 100000.into()),
         )));
 
         assert_eq!(from_string_0_2348923985e5, manual_0_2348923985e5);
@@ -0,0 +1,116 @@
+use std::fmt::Display;
+
+use egg::Id;
+use num::Signed;
+use serde::{Deserialize, Serialize};
+
+use crate::{JuniperBigRational, MathExpression};
+
+// the number domains Juniper distinguishes between, ordered by inclusion (ℕ ⊂ ℤ ⊂ ℚ ⊂ ℝ)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Sort {
+    Nat,
+    Int,
+    Rat,
+    Real,
+}
+
+impl Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Sort::Nat => "ℕ",
+                Sort::Int => "ℤ",
+                Sort::Rat => "ℚ",
+                Sort::Real => "ℝ",
+            }
+        )
+    }
+}
+
+impl Sort {
+    // the smallest sort containing the given constant
+    pub fn of_constant(constant: &JuniperBigRational) -> Sort {
+        if !constant.0.is_integer() {
+            Sort::Rat
+        } else if constant.0.is_negative() {
+            Sort::Int
+        } else {
+            Sort::Nat
+        }
+    }
+
+    // the smallest sort guaranteed to contain the value of an expression node, given the sorts of
+    // its children (variables are assumed to be real)
+    pub fn of_node(node: &MathExpression, child: impl Fn(&Id) -> Sort) -> Sort {
+        match node {
+            MathExpression::Constant(c) => Sort::of_constant(c),
+            MathExpression::Add([a, b]) | MathExpression::Mul([a, b]) => child(a).max(child(b)),
+            MathExpression::Sub([a, b]) => child(a).max(child(b)).max(Sort::Int),
+            MathExpression::Div([a, b]) => child(a).max(child(b)).max(Sort::Rat),
+            MathExpression::Neg(a) => child(a).max(Sort::Int),
+            MathExpression::Inv(a) => child(a).max(Sort::Rat),
+            MathExpression::Pow([a, b]) => match child(b) {
+                Sort::Nat => child(a),
+                Sort::Int => child(a).max(Sort::Rat),
+                _ => Sort::Real,
+            },
+            // a cast's result is only known to be in its target sort, which could be ℝ. (This also
+            // stops a cast lemma from casting its own result again, since only its argument is in
+            // the smaller sort)
+            MathExpression::Cast(_) => Sort::Real,
+            _ => Sort::Real,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use egg::RecExpr;
+
+    use crate::MathExpression;
+
+    use super::Sort;
+
+    fn sort_of(s: &str) -> Result<Sort> {
+        let re: RecExpr<MathExpression> = s.parse()?;
+        let mut sorts = Vec::new();
+        for node in re.as_ref() {
+            let sort = Sort::of_node(node, |id| sorts[usize::from(*id)]);
+            sorts.push(sort);
+        }
+        Ok(*sorts.last().expect("empty expression"))
+    }
+
+    #[test]
+    fn test_sort_constants() -> Result<()> {
+        assert_eq!(sort_of("5")?, Sort::Nat);
+        assert_eq!(sort_of("0")?, Sort::Nat);
+        assert_eq!(sort_of("-3")?, Sort::Int);
+        assert_eq!(sort_of("1/2")?, Sort::Rat);
+        assert_eq!(sort_of("π")?, Sort::Real);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_operators() -> Result<()> {
+        assert_eq!(sort_of("(+ 1 2)")?, Sort::Nat);
+        assert_eq!(sort_of("(- 1 2)")?, Sort::Int);
+        assert_eq!(sort_of("(- 1)")?, Sort::Int);
+        assert_eq!(sort_of("(/ 1 2)")?, Sort::Rat);
+        assert_eq!(sort_of("(inv 2)")?, Sort::Rat);
+        assert_eq!(sort_of("(^ 2 3)")?, Sort::Nat);
+        assert_eq!(sort_of("(^ 2 (- 3))")?, Sort::Rat);
+        assert_eq!(sort_of("(^ 2 1/2)")?, Sort::Real);
+        assert_eq!(sort_of("(sqrt 4)")?, Sort::Real);
+        assert_eq!(sort_of("(sin x)")?, Sort::Real);
+        assert_eq!(sort_of("(* x 2)")?, Sort::Real);
+        assert_eq!(sort_of("(cast 3)")?, Sort::Real);
+
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
-use anyhow::{Error, Result};
-use egg::{AstSize, Extractor, Id, Language, Pattern, RecExpr, Rewrite};
+use ::std::{fs, io};
+use anyhow::{Context, Error, Result};
+use egg::{AstDepth, AstSize, CostFunction, Id, Language, Pattern, RecExpr, Rewrite};
 use juniper_lib::{
-    approximate, get_juniper_rules, is_atomic, JuniperRewrite, JuniperRunner, MathExpression,
+    approximate, collect, expand, is_atomic, solve, Constrained, ConversionReport,
+    CounterexampleSearch, Forbidden, Forbidding, JuniperRewrite, MathExpression, NumericCheck,
+    Proof, RuleCache, Simplifier, SolutionSet, TheoremSet, VariableOccurrences, WeightedCost,
+    EMBEDDED_EXPORT,
 };
-use::std::io;
 
 // courtesy of Remy Wang on the E-Graphs Zulip
 fn split<L: Language>(e: &RecExpr<L>) -> Vec<RecExpr<L>> {
@@ -23,7 +26,7 @@ fn create_assignment(
         MathExpression::Assign(_) => {
             let children = split(expr);
 
-            if let Some(side_a) = children.get(0) {
+            if let Some(side_a) = children.first() {
                 if let Some(side_b) = children.get(1) {
                     let pattern_a: Pattern<MathExpression> = side_a.into();
                     let pattern_b: Pattern<MathExpression> = side_b.into();
@@ -59,22 +62,372 @@ fn create_assignment(
     }
 }
 
+// `--rules <file.json>` (which may be repeated) replaces the embedded theorems with the given
+// exports, and `--cache <file>` keeps their converted rules in a cache which is only rebuilt when
+// the exports change
+fn load_rules(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(Vec<JuniperRewrite>, ConversionReport)> {
+    let mut paths = Vec::new();
+    let mut cache = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => match args.next() {
+                Some(path) => paths.push(path),
+                None => return Err(Error::msg("--rules expects a file")),
+            },
+            "--cache" => match args.next() {
+                Some(path) => cache = Some(path),
+                None => return Err(Error::msg("--cache expects a file")),
+            },
+            _ => return Err(Error::msg(format!("unknown argument: {arg}"))),
+        }
+    }
+
+    let sources = if paths.is_empty() {
+        vec![EMBEDDED_EXPORT.to_string()]
+    } else {
+        paths
+            .iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .with_context(|| format!("couldn't read theorem file {path}"))
+            })
+            .collect::<Result<_>>()?
+    };
+    let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+
+    match cache {
+        Some(cache) => {
+            let cache = RuleCache::load_or_build(&sources, cache)?;
+            Ok((cache.rules()?, cache.report().clone()))
+        }
+        None => {
+            let mut theorems = TheoremSet::default();
+            for source in sources {
+                theorems.merge(TheoremSet::from_json_str(source)?)?;
+            }
+            Ok(theorems.rules_lenient())
+        }
+    }
+}
+
+// up to k distinct equivalent expressions which avoid the forbidden operators and variables,
+// cheapest first
+fn alternatives<C: CostFunction<MathExpression>>(
+    simplifier: &Simplifier,
+    expr: &RecExpr<MathExpression>,
+    cost_function: C,
+    forbidden: &Forbidden,
+    k: usize,
+) -> Vec<RecExpr<MathExpression>> {
+    simplifier
+        .alternatives_with(expr, Forbidding::new(cost_function, forbidden.clone()), k)
+        .into_iter()
+        .filter(|(cost, _)| matches!(cost, Constrained::Allowed(_)))
+        .map(|(_, expr)| expr)
+        .collect()
+}
+
+// the cost function expressions are extracted with, chosen with `:cost <name>` (and `:weight
+// <operator> <weight>`, which switches to the weighted cost)
+enum Cost {
+    Size,
+    Depth,
+    Weighted(WeightedCost),
+    Variables,
+}
+
+impl Cost {
+    // the cheapest equivalent expression which avoids the forbidden operators and variables (set
+    // with `:avoid`)
+    fn simplify(
+        &self,
+        simplifier: &Simplifier,
+        expr: &RecExpr<MathExpression>,
+        forbidden: &Forbidden,
+    ) -> Result<RecExpr<MathExpression>> {
+        Ok(match self {
+            Cost::Size => {
+                simplifier
+                    .simplify_avoiding_with(expr, AstSize, forbidden)?
+                    .best
+            }
+            Cost::Depth => {
+                simplifier
+                    .simplify_avoiding_with(expr, AstDepth, forbidden)?
+                    .best
+            }
+            Cost::Weighted(weighted) => {
+                simplifier
+                    .simplify_avoiding_with(expr, weighted.clone(), forbidden)?
+                    .best
+            }
+            Cost::Variables => {
+                simplifier
+                    .simplify_avoiding_with(expr, VariableOccurrences, forbidden)?
+                    .best
+            }
+        })
+    }
+
+    fn alternatives(
+        &self,
+        simplifier: &Simplifier,
+        expr: &RecExpr<MathExpression>,
+        forbidden: &Forbidden,
+        k: usize,
+    ) -> Vec<RecExpr<MathExpression>> {
+        match self {
+            Cost::Size => alternatives(simplifier, expr, AstSize, forbidden, k),
+            Cost::Depth => alternatives(simplifier, expr, AstDepth, forbidden, k),
+            Cost::Weighted(weighted) => {
+                alternatives(simplifier, expr, weighted.clone(), forbidden, k)
+            }
+            Cost::Variables => alternatives(simplifier, expr, VariableOccurrences, forbidden, k),
+        }
+    }
+}
+
+// handle a `:prove (= <lhs> <rhs>)` command
+fn prove(simplifier: &Simplifier, command: &str) -> Result<()> {
+    let equation: RecExpr<MathExpression> = command.trim_start_matches(":prove").parse()?;
+    let (lhs, rhs) = match (&equation[equation.root()], split(&equation).as_slice()) {
+        (MathExpression::Eq(_), [lhs, rhs]) => (lhs.clone(), rhs.clone()),
+        _ => return Err(Error::msg("usage: :prove (= <lhs> <rhs>)")),
+    };
+
+    // there's no point saturating if the sides are numerically different
+    let check = CounterexampleSearch::new().check(&lhs, &rhs);
+    if let NumericCheck::Counterexample { .. } = check {
+        println!("not equal, {check}");
+        return Ok(());
+    }
+
+    let proof = simplifier.prove_equal(&lhs, &rhs);
+    println!("{proof}");
+    match &proof {
+        Proof::Proved { explanation, .. } => println!("{explanation}"),
+        Proof::Inconclusive { .. } => println!("{check}"),
+    }
+    Ok(())
+}
+
+// handle a `:solve <variable> (= <lhs> <rhs>)` command
+fn solve_command(command: &str) -> Result<()> {
+    let usage = || Error::msg("usage: :solve <variable> (= <lhs> <rhs>)");
+    let (var, equation) = command
+        .trim_start_matches(":solve")
+        .trim()
+        .split_once(' ')
+        .ok_or_else(usage)?;
+    let var = match var.chars().collect::<Vec<_>>().as_slice() {
+        [var] => *var,
+        _ => return Err(usage()),
+    };
+
+    match solve(&equation.parse()?, var)? {
+        SolutionSet::Finite(solutions) if solutions.is_empty() => println!("no solutions"),
+        SolutionSet::Finite(solutions) => {
+            for solution in solutions {
+                println!("{var} = {solution}");
+            }
+        }
+        SolutionSet::All => println!("every {var} is a solution"),
+    }
+    Ok(())
+}
+
+// handle a `:linear <unknowns> <equations>` command, such as `:linear x y (= (+ x y) 3) (= x 1)`
+fn linear_command(simplifier: &Simplifier, command: &str) -> Result<()> {
+    let usage = || Error::msg("usage: :linear <unknowns> (= <lhs> <rhs>) (= <lhs> <rhs>) ...");
+    let command = command.trim_start_matches(":linear");
+    let (unknowns, mut rest) = command.split_at(command.find('(').ok_or_else(usage)?);
+    let unknowns = unknowns
+        .split_whitespace()
+        .map(
+            |unknown| match unknown.chars().collect::<Vec<_>>().as_slice() {
+                [unknown] => Ok(*unknown),
+                _ => Err(usage()),
+            },
+        )
+        .collect::<Result<Vec<_>>>()?;
+
+    // split the rest into its top level s-expressions
+    let mut equations = Vec::new();
+    while !rest.trim().is_empty() {
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find_map(|(i, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                (depth == 0 && c == ')').then_some(i + 1)
+            })
+            .ok_or_else(usage)?;
+        equations.push(rest[..end].parse()?);
+        rest = &rest[end..];
+    }
+
+    println!("{}", simplifier.solve_linear_system(&equations, &unknowns)?);
+    Ok(())
+}
+
+// handle a `:collect <variable> <expr>` command
+fn collect_command(command: &str) -> Result<()> {
+    let usage = || Error::msg("usage: :collect <variable> <expr>");
+    let (var, expr) = command
+        .trim_start_matches(":collect")
+        .trim()
+        .split_once(' ')
+        .ok_or_else(usage)?;
+    let var = match var.chars().collect::<Vec<_>>().as_slice() {
+        [var] => *var,
+        _ => return Err(usage()),
+    };
+    println!("{}", collect(&expr.parse()?, var));
+    Ok(())
+}
+
+// handle a `:cost` or `:weight` command
+fn set_cost(cost: &mut Cost, command: &str) -> Result<()> {
+    match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [":cost", "size"] => *cost = Cost::Size,
+        [":cost", "depth"] => *cost = Cost::Depth,
+        [":cost", "weighted"] => *cost = Cost::Weighted(WeightedCost::default()),
+        [":cost", "variables"] => *cost = Cost::Variables,
+        [":cost", name] => return Err(Error::msg(format!("unknown cost function: {name}"))),
+        [":weight", operator, weight] => {
+            let operator = operator.parse()?;
+            let weight = weight.parse()?;
+            let weighted = match cost {
+                Cost::Weighted(weighted) => weighted.clone(),
+                _ => WeightedCost::default(),
+            };
+            *cost = Cost::Weighted(weighted.with_weight(operator, weight)?);
+        }
+        _ => {
+            return Err(Error::msg(
+                "usage: :cost size|depth|weighted|variables, :weight <operator> <weight> \
+                 (other cost functions can only be used through juniper_lib)",
+            ))
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
-    let mut rules = get_juniper_rules()?;
+    let (rules, report) = load_rules(std::env::args().skip(1))?;
+    let mut simplifier = Simplifier::new(rules);
     let mut conditions = Vec::new();
+    let mut cost = Cost::Size;
+    let mut forbidden = Forbidden::new();
+    // how many equivalent forms to show, set with `:alternatives <k>`
+    let mut shown = 1;
+
+    let failed = report.failed().count();
+    if failed > 0 {
+        println!("warning: {failed} theorem(s) could not be converted (see :report)");
+    }
 
     loop {
         println!("Enter a (lisp-y) expression: ");
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
             Ok(_) => {
-                let expr: RecExpr<MathExpression> = input.parse()?;
+                match input.trim() {
+                    ":report" => {
+                        println!("{report}");
+                        continue;
+                    }
+                    command if command.starts_with(":cost") || command.starts_with(":weight") => {
+                        if let Err(error) = set_cost(&mut cost, command) {
+                            println!("error: {error}");
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":avoid") => {
+                        // `:avoid <operators and variables>`, or just `:avoid` to allow everything
+                        match command.trim_start_matches(":avoid").parse() {
+                            Ok(avoid) => forbidden = avoid,
+                            Err(error) => println!("error: {error}"),
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":prove") => {
+                        if let Err(error) = prove(&simplifier, command) {
+                            println!("error: {error}");
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":solve") => {
+                        if let Err(error) = solve_command(command) {
+                            println!("error: {error}");
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":expand") => {
+                        match command.trim_start_matches(":expand").parse() {
+                            Ok(expr) => println!("{}", expand(&expr)),
+                            Err(error) => println!("error: {error}"),
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":collect") => {
+                        if let Err(error) = collect_command(command) {
+                            println!("error: {error}");
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":linear") => {
+                        if let Err(error) = linear_command(&simplifier, command) {
+                            println!("error: {error}");
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(":alternatives") => {
+                        match command.trim_start_matches(":alternatives").trim().parse() {
+                            Ok(k) if k > 0 => shown = k,
+                            _ => println!("usage: :alternatives <k>, with k at least 1"),
+                        }
+                        continue;
+                    }
+                    command if command.starts_with(':') => {
+                        println!("unknown command: {command}");
+                        continue;
+                    }
+                    _ => {}
+                }
 
-                let runner = JuniperRunner::default().with_expr(&expr).run(&rules);
-                let extractor = Extractor::new(&runner.egraph, AstSize);
+                let expr: RecExpr<MathExpression> = input.parse()?;
 
-                let (_, best_expr) = extractor.find_best(runner.roots[0]);
-                println!("{}", best_expr);
+                let best_expr = if shown > 1 {
+                    let forms = cost.alternatives(&simplifier, &expr, &forbidden, shown);
+                    for (i, form) in forms.iter().enumerate() {
+                        println!("{}. {}", i + 1, form);
+                    }
+                    match forms.into_iter().next() {
+                        Some(best_expr) => best_expr,
+                        None => {
+                            println!("error: no equivalent expression avoids {forbidden}");
+                            continue;
+                        }
+                    }
+                } else {
+                    match cost.simplify(&simplifier, &expr, &forbidden) {
+                        Ok(best_expr) => {
+                            println!("{}", best_expr);
+                            best_expr
+                        }
+                        Err(error) => {
+                            println!("error: {error}");
+                            continue;
+                        }
+                    }
+                };
 
                 if !is_atomic(&best_expr, &best_expr.root()) {
                     if let Some(approximation) = approximate(&best_expr, &best_expr.root()) {
@@ -84,7 +437,7 @@ fn main() -> Result<()> {
 
                 if let Ok(Some(rewrites)) = create_assignment(conditions.len(), &expr) {
                     conditions.extend_from_slice(&rewrites);
-                    rules.extend_from_slice(&rewrites);
+                    simplifier.add_rules(rewrites);
                 }
             }
             Err(error) => println!("error: {error}"),
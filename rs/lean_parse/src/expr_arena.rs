@@ -0,0 +1,603 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+use std::ops::Index;
+
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::lean_expr::{BinderInfo, FVarId, LeanExpr, Level, Literal, MData, MVarId, Name};
+
+// a reference to an expression stored in an ExprArena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExprId(u32);
+
+impl ExprId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// a single LeanExpr node, whose subexpressions are references into an ExprArena
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ExprNode {
+    BVar {
+        de_bruijn_index: u64,
+    },
+    FVar {
+        fvar_id: FVarId,
+    },
+    MVar {
+        mvar_id: MVarId,
+    },
+    Sort {
+        u: Level,
+    },
+    Const {
+        decl_name: Name,
+        us: Vec<Level>,
+    },
+    App {
+        function: ExprId,
+        arg: ExprId,
+    },
+    Lam {
+        binder_name: Name,
+        binder_type: ExprId,
+        body: ExprId,
+        binder_info: BinderInfo,
+    },
+    ForallE {
+        binder_name: Name,
+        binder_type: ExprId,
+        body: ExprId,
+        binder_info: BinderInfo,
+    },
+    LetE {
+        decl_name: Name,
+        typ: ExprId,
+        value: ExprId,
+        body: ExprId,
+        non_dep: bool,
+    },
+    Lit(Literal),
+    MData {
+        data: MData,
+        expr: ExprId,
+    },
+    Proj {
+        type_name: Name,
+        idx: u64,
+        structure: ExprId,
+    },
+}
+
+// hash-consed storage for LeanExprs: structurally equal subexpressions are only stored once, so
+// an expression is a DAG of ExprIds rather than a tree of Boxes
+#[derive(Debug, Clone, Default)]
+pub struct ExprArena {
+    nodes: Vec<ExprNode>,
+    // the ids of the stored nodes, bucketed by the hash of their node
+    buckets: HashMap<u64, Vec<ExprId>>,
+}
+
+impl ExprArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the number of distinct nodes stored
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // store a node, returning the id of an equal node if there already is one
+    pub fn add(&mut self, node: ExprNode) -> ExprId {
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+        let bucket = self.buckets.entry(hasher.finish()).or_default();
+
+        if let Some(id) = bucket.iter().find(|id| self.nodes[id.index()] == node) {
+            return *id;
+        }
+        let id = ExprId(
+            u32::try_from(self.nodes.len()).expect("more than u32::MAX distinct expressions"),
+        );
+        self.nodes.push(node);
+        bucket.push(id);
+        id
+    }
+
+    // store a boxed expression tree, sharing any subexpressions already in the arena
+    pub fn intern(&mut self, expr: &LeanExpr) -> ExprId {
+        let node = match expr {
+            LeanExpr::BVar { de_bruijn_index } => ExprNode::BVar {
+                de_bruijn_index: *de_bruijn_index,
+            },
+            LeanExpr::FVar { fvar_id } => ExprNode::FVar {
+                fvar_id: fvar_id.clone(),
+            },
+            LeanExpr::MVar { mvar_id } => ExprNode::MVar {
+                mvar_id: mvar_id.clone(),
+            },
+            LeanExpr::Sort { u } => ExprNode::Sort { u: u.clone() },
+            LeanExpr::Const { decl_name, us } => ExprNode::Const {
+                decl_name: decl_name.clone(),
+                us: us.clone(),
+            },
+            LeanExpr::App { function, arg } => ExprNode::App {
+                function: self.intern(function),
+                arg: self.intern(arg),
+            },
+            LeanExpr::Lam {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => ExprNode::Lam {
+                binder_name: binder_name.clone(),
+                binder_type: self.intern(binder_type),
+                body: self.intern(body),
+                binder_info: binder_info.clone(),
+            },
+            LeanExpr::ForallE {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => ExprNode::ForallE {
+                binder_name: binder_name.clone(),
+                binder_type: self.intern(binder_type),
+                body: self.intern(body),
+                binder_info: binder_info.clone(),
+            },
+            LeanExpr::LetE {
+                decl_name,
+                typ,
+                value,
+                body,
+                non_dep,
+            } => ExprNode::LetE {
+                decl_name: decl_name.clone(),
+                typ: self.intern(typ),
+                value: self.intern(value),
+                body: self.intern(body),
+                non_dep: *non_dep,
+            },
+            LeanExpr::Lit(literal) => ExprNode::Lit(literal.clone()),
+            LeanExpr::MData { data, expr } => ExprNode::MData {
+                data: data.clone(),
+                expr: self.intern(expr),
+            },
+            LeanExpr::Proj {
+                type_name,
+                idx,
+                structure,
+            } => ExprNode::Proj {
+                type_name: type_name.clone(),
+                idx: *idx,
+                structure: self.intern(structure),
+            },
+        };
+        self.add(node)
+    }
+
+    // rebuild the boxed expression tree an id refers to. Shared subexpressions are copied wherever
+    // they occur, so this takes time (and memory) linear in the size of the tree, not the arena
+    pub fn to_expr(&self, id: ExprId) -> LeanExpr {
+        let child = |id: &ExprId| Box::new(self.to_expr(*id));
+        match &self[id] {
+            ExprNode::BVar { de_bruijn_index } => LeanExpr::BVar {
+                de_bruijn_index: *de_bruijn_index,
+            },
+            ExprNode::FVar { fvar_id } => LeanExpr::FVar {
+                fvar_id: fvar_id.clone(),
+            },
+            ExprNode::MVar { mvar_id } => LeanExpr::MVar {
+                mvar_id: mvar_id.clone(),
+            },
+            ExprNode::Sort { u } => LeanExpr::Sort { u: u.clone() },
+            ExprNode::Const { decl_name, us } => LeanExpr::Const {
+                decl_name: decl_name.clone(),
+                us: us.clone(),
+            },
+            ExprNode::App { function, arg } => LeanExpr::App {
+                function: child(function),
+                arg: child(arg),
+            },
+            ExprNode::Lam {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => LeanExpr::Lam {
+                binder_name: binder_name.clone(),
+                binder_type: child(binder_type),
+                body: child(body),
+                binder_info: binder_info.clone(),
+            },
+            ExprNode::ForallE {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => LeanExpr::ForallE {
+                binder_name: binder_name.clone(),
+                binder_type: child(binder_type),
+                body: child(body),
+                binder_info: binder_info.clone(),
+            },
+            ExprNode::LetE {
+                decl_name,
+                typ,
+                value,
+                body,
+                non_dep,
+            } => LeanExpr::LetE {
+                decl_name: decl_name.clone(),
+                typ: child(typ),
+                value: child(value),
+                body: child(body),
+                non_dep: *non_dep,
+            },
+            ExprNode::Lit(literal) => LeanExpr::Lit(literal.clone()),
+            ExprNode::MData { data, expr } => LeanExpr::MData {
+                data: data.clone(),
+                expr: child(expr),
+            },
+            ExprNode::Proj {
+                type_name,
+                idx,
+                structure,
+            } => LeanExpr::Proj {
+                type_name: type_name.clone(),
+                idx: *idx,
+                structure: child(structure),
+            },
+        }
+    }
+
+    // read a single JSON-exported Expr straight into the arena, without building its tree
+    pub fn read_expr_json(&mut self, json: &str) -> serde_json::Result<ExprId> {
+        ExprSeed(self).deserialize(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    // stream a JSON array of `{"name": ..., "type": ...}` declarations (the format of
+    // exported.json) into the arena
+    pub fn read_declarations_json(
+        &mut self,
+        reader: impl Read,
+    ) -> serde_json::Result<Vec<(Name, ExprId)>> {
+        DeclarationsSeed(self).deserialize(&mut serde_json::Deserializer::from_reader(reader))
+    }
+}
+
+impl Index<ExprId> for ExprArena {
+    type Output = ExprNode;
+
+    fn index(&self, id: ExprId) -> &ExprNode {
+        &self.nodes[id.index()]
+    }
+}
+
+// deserializes a JSON-exported Expr into an arena, node by node
+pub struct ExprSeed<'a>(pub &'a mut ExprArena);
+
+impl<'de> DeserializeSeed<'de> for ExprSeed<'_> {
+    type Value = ExprId;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<ExprId, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ExprSeed<'_> {
+    type Value = ExprId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a Lean Expr")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ExprId, A::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct BVarFields {
+            de_bruijn_index: u64,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct FVarFields {
+            fvar_id: FVarId,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct MVarFields {
+            mvar_id: MVarId,
+        }
+        #[derive(Deserialize)]
+        struct SortFields {
+            u: Level,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ConstFields {
+            decl_name: Name,
+            us: Vec<Level>,
+        }
+
+        let tag: String = map
+            .next_key()?
+            .ok_or_else(|| de::Error::invalid_length(0, &"an Expr constructor"))?;
+        let node = match tag.as_str() {
+            "bvar" => {
+                let BVarFields { de_bruijn_index } = map.next_value()?;
+                ExprNode::BVar { de_bruijn_index }
+            }
+            "fvar" => {
+                let FVarFields { fvar_id } = map.next_value()?;
+                ExprNode::FVar { fvar_id }
+            }
+            "mvar" => {
+                let MVarFields { mvar_id } = map.next_value()?;
+                ExprNode::MVar { mvar_id }
+            }
+            "sort" => {
+                let SortFields { u } = map.next_value()?;
+                ExprNode::Sort { u }
+            }
+            "const" => {
+                let ConstFields { decl_name, us } = map.next_value()?;
+                ExprNode::Const { decl_name, us }
+            }
+            "lit" => ExprNode::Lit(map.next_value()?),
+            "app" | "lam" | "forallE" | "letE" | "mdata" | "proj" => {
+                let fields = map.next_value_seed(CompoundSeed(self.0))?;
+                fields.into_node(&tag)?
+            }
+            _ => {
+                return Err(de::Error::unknown_variant(
+                    &tag,
+                    &[
+                        "bvar", "fvar", "mvar", "sort", "const", "app", "lam", "forallE", "letE",
+                        "lit", "mdata", "proj",
+                    ],
+                ))
+            }
+        };
+
+        Ok(self.0.add(node))
+    }
+}
+
+// the fields of any of the Expr constructors with subexpressions, whose subexpressions are added
+// to the arena as they're read (in whatever order the JSON has them)
+#[derive(Default)]
+struct CompoundFields {
+    function: Option<ExprId>,
+    arg: Option<ExprId>,
+    binder_name: Option<Name>,
+    binder_type: Option<ExprId>,
+    body: Option<ExprId>,
+    binder_info: Option<BinderInfo>,
+    decl_name: Option<Name>,
+    typ: Option<ExprId>,
+    value: Option<ExprId>,
+    non_dep: Option<bool>,
+    data: Option<MData>,
+    expr: Option<ExprId>,
+    type_name: Option<Name>,
+    idx: Option<u64>,
+    structure: Option<ExprId>,
+}
+
+fn required<T, E: de::Error>(field: Option<T>, name: &'static str) -> Result<T, E> {
+    field.ok_or_else(|| E::missing_field(name))
+}
+
+impl CompoundFields {
+    fn into_node<E: de::Error>(self, tag: &str) -> Result<ExprNode, E> {
+        Ok(match tag {
+            "app" => ExprNode::App {
+                function: required(self.function, "fn")?,
+                arg: required(self.arg, "arg")?,
+            },
+            "lam" => ExprNode::Lam {
+                binder_name: required(self.binder_name, "binderName")?,
+                binder_type: required(self.binder_type, "binderType")?,
+                body: required(self.body, "body")?,
+                binder_info: required(self.binder_info, "binderInfo")?,
+            },
+            "forallE" => ExprNode::ForallE {
+                binder_name: required(self.binder_name, "binderName")?,
+                binder_type: required(self.binder_type, "binderType")?,
+                body: required(self.body, "body")?,
+                binder_info: required(self.binder_info, "binderInfo")?,
+            },
+            "letE" => ExprNode::LetE {
+                decl_name: required(self.decl_name, "declName")?,
+                typ: required(self.typ, "type")?,
+                value: required(self.value, "value")?,
+                body: required(self.body, "body")?,
+                non_dep: required(self.non_dep, "nonDep")?,
+            },
+            "mdata" => ExprNode::MData {
+                data: required(self.data, "data")?,
+                expr: required(self.expr, "expr")?,
+            },
+            _ => ExprNode::Proj {
+                type_name: required(self.type_name, "typeName")?,
+                idx: required(self.idx, "idx")?,
+                structure: required(self.structure, "struct")?,
+            },
+        })
+    }
+}
+
+struct CompoundSeed<'a>(&'a mut ExprArena);
+
+impl<'de> DeserializeSeed<'de> for CompoundSeed<'_> {
+    type Value = CompoundFields;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CompoundSeed<'_> {
+    type Value = CompoundFields;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the fields of a Lean Expr constructor")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CompoundFields, A::Error> {
+        let mut fields = CompoundFields::default();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "fn" => fields.function = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "arg" => fields.arg = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "binderType" => fields.binder_type = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "body" => fields.body = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "type" => fields.typ = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "value" => fields.value = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "expr" => fields.expr = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "struct" => fields.structure = Some(map.next_value_seed(ExprSeed(self.0))?),
+                "binderName" => fields.binder_name = Some(map.next_value()?),
+                "binderInfo" => fields.binder_info = Some(map.next_value()?),
+                "declName" => fields.decl_name = Some(map.next_value()?),
+                "nonDep" => fields.non_dep = Some(map.next_value()?),
+                "data" => fields.data = Some(map.next_value()?),
+                "typeName" => fields.type_name = Some(map.next_value()?),
+                "idx" => fields.idx = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(fields)
+    }
+}
+
+// deserializes an array of `{"name": ..., "type": ...}` declarations into an arena
+struct DeclarationsSeed<'a>(&'a mut ExprArena);
+
+impl<'de> DeserializeSeed<'de> for DeclarationsSeed<'_> {
+    type Value = Vec<(Name, ExprId)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DeclarationsSeed<'_> {
+    type Value = Vec<(Name, ExprId)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of declarations")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut declarations = Vec::new();
+        while let Some(declaration) = seq.next_element_seed(DeclarationSeed(self.0))? {
+            declarations.push(declaration);
+        }
+        Ok(declarations)
+    }
+}
+
+struct DeclarationSeed<'a>(&'a mut ExprArena);
+
+impl<'de> DeserializeSeed<'de> for DeclarationSeed<'_> {
+    type Value = (Name, ExprId);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DeclarationSeed<'_> {
+    type Value = (Name, ExprId);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a declaration with a name and type")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut name = None;
+        let mut typ = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => name = Some(map.next_value()?),
+                "type" => typ = Some(map.next_value_seed(ExprSeed(self.0))?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok((required(name, "name")?, required(typ, "type")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lean_expr::LeanExpr;
+
+    use super::{ExprArena, ExprNode};
+
+    #[test]
+    fn test_intern_round_trip() {
+        let json = include_str!("../../test_assets/mul_comm.json");
+        let mul_comm = serde_json::from_str::<LeanExpr>(json).unwrap();
+        let mut arena = ExprArena::new();
+        let id = arena.intern(&mul_comm);
+
+        assert_eq!(arena.to_expr(id), mul_comm);
+        // interning again adds nothing
+        let len = arena.len();
+        assert_eq!(arena.intern(&mul_comm), id);
+        assert_eq!(arena.len(), len);
+    }
+
+    #[test]
+    fn test_sharing() {
+        let json = include_str!("../../test_assets/mul_comm.json");
+        let mut arena = ExprArena::new();
+        let id = arena.read_expr_json(json).unwrap();
+
+        // `Rat` occurs many times in the tree, but only once in the arena
+        let rats = (0..arena.len())
+            .filter(|i| matches!(&arena.nodes[*i], ExprNode::Const { decl_name, .. } if *decl_name == "Rat"))
+            .count();
+        assert_eq!(rats, 1);
+
+        // streaming deserialization agrees with deserializing the tree
+        let mul_comm = serde_json::from_str::<LeanExpr>(json).unwrap();
+        assert_eq!(arena.to_expr(id), mul_comm);
+        assert_eq!(arena.intern(&mul_comm), id);
+    }
+
+    #[test]
+    fn test_read_declarations() {
+        let json = format!(
+            r#"[{{"name": "Rat.add_zero", "type": {}}}, {{"type": {}, "name": "Rat.mul_comm"}}]"#,
+            include_str!("../../test_assets/add_zero.json"),
+            include_str!("../../test_assets/mul_comm.json")
+        );
+        let mut arena = ExprArena::new();
+        let declarations = arena.read_declarations_json(json.as_bytes()).unwrap();
+
+        assert_eq!(declarations.len(), 2);
+        assert_eq!(declarations[0].0, "Rat.add_zero");
+        assert_eq!(declarations[1].0, "Rat.mul_comm");
+        assert_eq!(
+            arena.to_expr(declarations[0].1),
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero.json"))
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::lean_expr::{DataValue, FVarId, LeanExpr, Level, Name, StringPos};
+
+impl LeanExpr {
+    // the direct subexpressions of this expression, each paired with the number of binders
+    // entered to reach it
+    pub fn children(&self) -> Vec<(&LeanExpr, u64)> {
+        match self {
+            LeanExpr::App { function, arg } => vec![(function, 0), (arg, 0)],
+            LeanExpr::Lam {
+                binder_type, body, ..
+            }
+            | LeanExpr::ForallE {
+                binder_type, body, ..
+            } => vec![(binder_type, 0), (body, 1)],
+            LeanExpr::LetE {
+                typ, value, body, ..
+            } => vec![(typ, 0), (value, 0), (body, 1)],
+            LeanExpr::MData { expr, .. } => vec![(expr, 0)],
+            LeanExpr::Proj { structure, .. } => vec![(structure, 0)],
+            _ => Vec::new(),
+        }
+    }
+
+    // rebuild this expression with f applied to each direct subexpression (along with the
+    // number of binders entered to reach it)
+    pub fn map_children(&self, mut f: impl FnMut(&LeanExpr, u64) -> LeanExpr) -> LeanExpr {
+        match self {
+            LeanExpr::App { function, arg } => LeanExpr::App {
+                function: Box::new(f(function, 0)),
+                arg: Box::new(f(arg, 0)),
+            },
+            LeanExpr::Lam {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => LeanExpr::Lam {
+                binder_name: binder_name.clone(),
+                binder_type: Box::new(f(binder_type, 0)),
+                body: Box::new(f(body, 1)),
+                binder_info: binder_info.clone(),
+            },
+            LeanExpr::ForallE {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => LeanExpr::ForallE {
+                binder_name: binder_name.clone(),
+                binder_type: Box::new(f(binder_type, 0)),
+                body: Box::new(f(body, 1)),
+                binder_info: binder_info.clone(),
+            },
+            LeanExpr::LetE {
+                decl_name,
+                typ,
+                value,
+                body,
+                non_dep,
+            } => LeanExpr::LetE {
+                decl_name: decl_name.clone(),
+                typ: Box::new(f(typ, 0)),
+                value: Box::new(f(value, 0)),
+                body: Box::new(f(body, 1)),
+                non_dep: *non_dep,
+            },
+            LeanExpr::MData { data, expr } => LeanExpr::MData {
+                data: data.clone(),
+                expr: Box::new(f(expr, 0)),
+            },
+            LeanExpr::Proj {
+                type_name,
+                idx,
+                structure,
+            } => LeanExpr::Proj {
+                type_name: type_name.clone(),
+                idx: *idx,
+                structure: Box::new(f(structure, 0)),
+            },
+            e => e.clone(),
+        }
+    }
+
+    // replace every subexpression for which f returns Some (f is given the number of binders
+    // entered so far, and isn't called on the subexpressions of anything it replaces)
+    pub fn replace(&self, f: &mut impl FnMut(&LeanExpr, u64) -> Option<LeanExpr>) -> LeanExpr {
+        self.replace_at(0, f)
+    }
+
+    fn replace_at(
+        &self,
+        offset: u64,
+        f: &mut impl FnMut(&LeanExpr, u64) -> Option<LeanExpr>,
+    ) -> LeanExpr {
+        match f(self, offset) {
+            Some(replaced) => replaced,
+            None => self.map_children(|child, binders| child.replace_at(offset + binders, f)),
+        }
+    }
+
+    // fold over every subexpression in pre-order, along with the number of binders entered to
+    // reach it
+    pub fn fold<T>(&self, init: T, f: &mut impl FnMut(T, &LeanExpr, u64) -> T) -> T {
+        self.fold_at(0, init, f)
+    }
+
+    fn fold_at<T>(&self, offset: u64, init: T, f: &mut impl FnMut(T, &LeanExpr, u64) -> T) -> T {
+        let acc = f(init, self, offset);
+        self.children()
+            .into_iter()
+            .fold(acc, |acc, (child, binders)| {
+                child.fold_at(offset + binders, acc, f)
+            })
+    }
+
+    // visit every subexpression in pre-order, only descending into the children of a
+    // subexpression if f returns true for it
+    pub fn visit(&self, f: &mut impl FnMut(&LeanExpr, u64) -> bool) {
+        self.visit_at(0, f)
+    }
+
+    fn visit_at(&self, offset: u64, f: &mut impl FnMut(&LeanExpr, u64) -> bool) {
+        if f(self, offset) {
+            for (child, binders) in self.children() {
+                child.visit_at(offset + binders, f);
+            }
+        }
+    }
+
+    // one more than the largest loose bound variable index (0 if there are none)
+    pub fn loose_bvar_range(&self) -> u64 {
+        self.fold(0, &mut |range, e, offset| match e {
+            LeanExpr::BVar { de_bruijn_index } if *de_bruijn_index >= offset => {
+                range.max(de_bruijn_index - offset + 1)
+            }
+            _ => range,
+        })
+    }
+
+    pub fn has_loose_bvars(&self) -> bool {
+        self.loose_bvar_range() > 0
+    }
+
+    // whether the loose bound variable with the given index occurs in this expression
+    pub fn has_loose_bvar(&self, index: u64) -> bool {
+        self.fold(false, &mut |found, e, offset| {
+            found || matches!(e, LeanExpr::BVar { de_bruijn_index } if *de_bruijn_index == index + offset)
+        })
+    }
+
+    // shift every loose bound variable at or above `start` up by `shift`
+    pub fn lift_loose_bvars(&self, start: u64, shift: u64) -> LeanExpr {
+        self.replace(&mut |e, offset| match e {
+            LeanExpr::BVar { de_bruijn_index } if *de_bruijn_index >= start + offset => {
+                Some(LeanExpr::BVar {
+                    de_bruijn_index: de_bruijn_index + shift,
+                })
+            }
+            _ => None,
+        })
+    }
+
+    // replace the loose bound variables 0..values.len() with the corresponding values (bound
+    // variable i becomes values[i]), lowering the remaining loose bound variables to match
+    pub fn instantiate(&self, values: &[LeanExpr]) -> LeanExpr {
+        let n = values.len() as u64;
+        self.replace(&mut |e, offset| match e {
+            LeanExpr::BVar { de_bruijn_index } if *de_bruijn_index >= offset => {
+                let index = de_bruijn_index - offset;
+                Some(match values.get(index as usize) {
+                    Some(value) => value.lift_loose_bvars(0, offset),
+                    None => LeanExpr::BVar {
+                        de_bruijn_index: de_bruijn_index - n,
+                    },
+                })
+            }
+            _ => None,
+        })
+    }
+
+    // instantiate the loose bound variable 0 (e.g. the body of a binder) with value
+    pub fn instantiate1(&self, value: &LeanExpr) -> LeanExpr {
+        self.instantiate(std::slice::from_ref(value))
+    }
+
+    // instantiate with values in binding order (bound variable i becomes values[len - 1 - i])
+    pub fn instantiate_rev(&self, values: &[LeanExpr]) -> LeanExpr {
+        let reversed: Vec<LeanExpr> = values.iter().rev().cloned().collect();
+        self.instantiate(&reversed)
+    }
+
+    // the inverse of instantiate_rev: replace each occurrence of fvars[i] with the bound variable
+    // which a binder for it would introduce, where fvars.last() is bound innermost
+    pub fn abstract_fvars(&self, fvars: &[FVarId]) -> LeanExpr {
+        let n = fvars.len() as u64;
+        self.replace(&mut |e, offset| match e {
+            LeanExpr::FVar { fvar_id } => {
+                fvars
+                    .iter()
+                    .rposition(|fvar| fvar == fvar_id)
+                    .map(|i| LeanExpr::BVar {
+                        de_bruijn_index: offset + n - 1 - i as u64,
+                    })
+            }
+            _ => None,
+        })
+    }
+
+    // the distinct free variables of this expression, in order of first occurrence
+    pub fn free_vars(&self) -> Vec<FVarId> {
+        self.fold(Vec::new(), &mut |mut fvars: Vec<FVarId>, e, _| {
+            if let LeanExpr::FVar { fvar_id } = e {
+                if !fvars.contains(fvar_id) {
+                    fvars.push(fvar_id.clone());
+                }
+            }
+            fvars
+        })
+    }
+
+    // the names of every constant this expression refers to
+    pub fn constants(&self) -> HashSet<Name> {
+        self.fold(HashSet::new(), &mut |mut constants: HashSet<Name>, e, _| {
+            if let LeanExpr::Const { decl_name, .. } = e {
+                constants.insert(decl_name.clone());
+            }
+            constants
+        })
+    }
+
+    // specialize a universe polymorphic expression, replacing each universe parameter params[i]
+    // in its sorts and constants by levels[i]
+    pub fn instantiate_level_params(&self, params: &[Name], levels: &[Level]) -> LeanExpr {
+        self.replace(&mut |e, _| match e {
+            LeanExpr::Sort { u } => Some(LeanExpr::Sort {
+                u: u.instantiate_params(params, levels),
+            }),
+            LeanExpr::Const { decl_name, us } => Some(LeanExpr::Const {
+                decl_name: decl_name.clone(),
+                us: us
+                    .iter()
+                    .map(|u| u.instantiate_params(params, levels))
+                    .collect(),
+            }),
+            _ => None,
+        })
+    }
+
+    // the head of an application spine (the expression itself if it isn't an application)
+    pub fn get_app_fn(&self) -> &LeanExpr {
+        match self {
+            LeanExpr::App { function, .. } => function.get_app_fn(),
+            e => e,
+        }
+    }
+
+    // the arguments of an application spine, in application order
+    pub fn get_app_args(&self) -> Vec<&LeanExpr> {
+        match self {
+            LeanExpr::App { function, arg } => {
+                let mut args = function.get_app_args();
+                args.push(arg);
+                args
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn get_app_num_args(&self) -> usize {
+        match self {
+            LeanExpr::App { function, .. } => function.get_app_num_args() + 1,
+            _ => 0,
+        }
+    }
+
+    // apply f to each of args in turn
+    pub fn mk_app(f: LeanExpr, args: impl IntoIterator<Item = LeanExpr>) -> LeanExpr {
+        args.into_iter().fold(f, |function, arg| LeanExpr::App {
+            function: Box::new(function),
+            arg: Box::new(arg),
+        })
+    }
+
+    // the source range of the first syntax attached to this expression as metadata, if any
+    pub fn source_range(&self) -> Option<(StringPos, StringPos)> {
+        self.fold(None, &mut |range, e, _| {
+            range.or_else(|| match e {
+                LeanExpr::MData { data, .. } => data.0.iter().find_map(|(_, value)| match value {
+                    DataValue::OfSyntax { v } => v.range(),
+                    _ => None,
+                }),
+                _ => None,
+            })
+        })
+    }
+
+    // a hash of the structure of this expression (structurally equal expressions hash equally)
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lean_expr::{BinderInfo, FVarId, LeanExpr, Name};
+
+    fn bvar(de_bruijn_index: u64) -> LeanExpr {
+        LeanExpr::BVar { de_bruijn_index }
+    }
+
+    fn fvar(name: &str) -> LeanExpr {
+        LeanExpr::FVar {
+            fvar_id: FVarId { name: name.into() },
+        }
+    }
+
+    fn constant(name: &str) -> LeanExpr {
+        LeanExpr::Const {
+            decl_name: name.into(),
+            us: Vec::new(),
+        }
+    }
+
+    fn lam(body: LeanExpr) -> LeanExpr {
+        LeanExpr::Lam {
+            binder_name: "x".into(),
+            binder_type: Box::new(constant("Real")),
+            body: Box::new(body),
+            binder_info: BinderInfo::Default,
+        }
+    }
+
+    #[test]
+    fn test_instantiate() {
+        // (f #0 (fun x => #0 #1 #2))[#0 := a] = f a (fun x => #0 a #1)
+        let f = constant("f");
+        let expr = LeanExpr::mk_app(
+            f.clone(),
+            [bvar(0), lam(LeanExpr::mk_app(bvar(0), [bvar(1), bvar(2)]))],
+        );
+        let expected = LeanExpr::mk_app(
+            f,
+            [
+                fvar("a"),
+                lam(LeanExpr::mk_app(bvar(0), [fvar("a"), bvar(1)])),
+            ],
+        );
+
+        assert_eq!(expr.instantiate1(&fvar("a")), expected);
+        assert_eq!(expr.loose_bvar_range(), 2);
+        assert_eq!(expected.loose_bvar_range(), 1);
+    }
+
+    #[test]
+    fn test_instantiate_lifts_values() {
+        // substituting a term with loose bound variables under a binder must lift them
+        let expr = lam(bvar(1));
+
+        assert_eq!(expr.instantiate1(&bvar(0)), lam(bvar(1)));
+        assert_eq!(expr.instantiate1(&bvar(3)), lam(bvar(4)));
+    }
+
+    #[test]
+    fn test_abstract() {
+        let a = FVarId { name: "a".into() };
+        let b = FVarId { name: "b".into() };
+        let expr = LeanExpr::mk_app(constant("f"), [fvar("a"), lam(fvar("b"))]);
+        let abstracted = expr.abstract_fvars(&[a.clone(), b.clone()]);
+
+        assert_eq!(
+            abstracted,
+            LeanExpr::mk_app(constant("f"), [bvar(1), lam(bvar(1))])
+        );
+        assert_eq!(expr.free_vars(), vec![a.clone(), b.clone()]);
+        assert_eq!(abstracted.instantiate(&[fvar("b"), fvar("a")]), expr);
+        assert_eq!(abstracted.instantiate_rev(&[fvar("a"), fvar("b")]), expr);
+    }
+
+    #[test]
+    fn test_app_spine() {
+        let expr = LeanExpr::mk_app(constant("f"), [fvar("a"), fvar("b")]);
+
+        assert_eq!(expr.get_app_fn(), &constant("f"));
+        assert_eq!(expr.get_app_args(), vec![&fvar("a"), &fvar("b")]);
+        assert_eq!(expr.get_app_num_args(), 2);
+        assert_eq!(expr.constants(), [Name::from("f")].into_iter().collect());
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let expr1 = LeanExpr::mk_app(constant("f"), [lam(bvar(0))]);
+        let expr2 = LeanExpr::mk_app(constant("f"), [lam(bvar(0))]);
+        let expr3 = LeanExpr::mk_app(constant("f"), [lam(bvar(1))]);
+
+        assert_eq!(expr1.structural_hash(), expr2.structural_hash());
+        assert_ne!(expr1.structural_hash(), expr3.structural_hash());
+    }
+}
@@ -0,0 +1,455 @@
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+
+use num::BigInt;
+
+use crate::expr_arena::{ExprArena, ExprId, ExprNode};
+use crate::lean_expr::{BinderInfo, Level, Literal, Name};
+
+// the kinds of declaration lean4export emits which carry a type (and possibly a value)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Axiom,
+    Definition,
+    Theorem,
+    Opaque,
+}
+
+// how readily the kernel unfolds a definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReducibilityHints {
+    Opaque,
+    Abbrev,
+    // definitions of greater height are unfolded first
+    Regular(u32),
+}
+
+// a declaration whose expressions are stored in its export's arena
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedDeclaration {
+    pub kind: DeclarationKind,
+    pub name: Name,
+    pub level_params: Vec<Name>,
+    pub typ: ExprId,
+    pub value: Option<ExprId>,
+    // only definitions have hints, and only lean4export versions which write them include them
+    pub hints: Option<ReducibilityHints>,
+}
+
+// the declarations of an export, along with the arena their expressions are stored in (which
+// keeps the export's own sharing, so subterms aren't copied for each expression using them)
+#[derive(Debug, Clone, Default)]
+pub struct Export {
+    pub arena: ExprArena,
+    pub declarations: Vec<ExportedDeclaration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportParseError {
+    // 1-indexed line of the export the error occurred on
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ExportParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ExportParseError {}
+
+// the hash-consed name, level and expression tables built up while reading an export, where
+// each entry may only refer to entries defined before it. Expressions are stored in an arena, and
+// the table maps the export's expression indices to them
+struct ExportTables {
+    names: Vec<Name>,
+    levels: Vec<Level>,
+    arena: ExprArena,
+    exprs: Vec<ExprId>,
+}
+
+// the whitespace separated fields of a single export line
+struct Fields<'a>(Peekable<SplitWhitespace<'a>>);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        self.0.next().ok_or_else(|| "missing field".to_string())
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        let field = self.next()?;
+        field
+            .parse()
+            .map_err(|_| format!("expected an index, found {field:?}"))
+    }
+
+    fn indices(&mut self) -> Result<Vec<usize>, String> {
+        self.0
+            .by_ref()
+            .map(|field| {
+                field
+                    .parse()
+                    .map_err(|_| format!("expected an index, found {field:?}"))
+            })
+            .collect()
+    }
+}
+
+// look up the entry a table index refers to
+fn lookup<'a, T>(table: &'a [T], index: usize, what: &str) -> Result<&'a T, String> {
+    table
+        .get(index)
+        .ok_or_else(|| format!("reference to undefined {what} {index}"))
+}
+
+// define the entry at index, which must be the next unused index of its table
+fn define<T>(table: &mut Vec<T>, index: usize, entry: T, what: &str) -> Result<(), String> {
+    if index != table.len() {
+        return Err(format!(
+            "{what} {index} defined out of order (expected {})",
+            table.len()
+        ));
+    }
+    table.push(entry);
+    Ok(())
+}
+
+fn binder_info(field: &str) -> Result<BinderInfo, String> {
+    match field {
+        "#BD" => Ok(BinderInfo::Default),
+        "#BI" => Ok(BinderInfo::Implicit),
+        "#BS" => Ok(BinderInfo::StrictImplicit),
+        "#BC" => Ok(BinderInfo::InstImplicit),
+        _ => Err(format!("unknown binder info {field:?}")),
+    }
+}
+
+impl ExportTables {
+    fn new() -> Self {
+        Self {
+            // name 0 and level 0 are implicitly the anonymous name and the zero level
+            names: vec![Name::Anonymous],
+            levels: vec![Level::Zero],
+            arena: ExprArena::new(),
+            exprs: Vec::new(),
+        }
+    }
+
+    fn name(&self, index: usize) -> Result<Name, String> {
+        lookup(&self.names, index, "name").cloned()
+    }
+
+    fn level(&self, index: usize) -> Result<Level, String> {
+        lookup(&self.levels, index, "level").cloned()
+    }
+
+    fn expr(&self, index: usize) -> Result<ExprId, String> {
+        lookup(&self.exprs, index, "expression").copied()
+    }
+
+    // read a `<index> #<kind> ...` table entry
+    fn read_entry(&mut self, index: usize, kind: &str, line: &str) -> Result<(), String> {
+        let mut fields = Fields(line.split_whitespace().peekable());
+        // the index and kind have already been read
+        fields.next()?;
+        fields.next()?;
+
+        match kind {
+            "#NS" => {
+                let pre = self.name(fields.index()?)?;
+                // string components run to the end of the line (and may contain spaces)
+                let str = line
+                    .splitn(4, char::is_whitespace)
+                    .nth(3)
+                    .ok_or("missing name component")?;
+                define(&mut self.names, index, Name::str(pre, str), "name")
+            }
+            "#NI" => {
+                let pre = self.name(fields.index()?)?;
+                let i = fields.next()?;
+                let i = i
+                    .parse()
+                    .map_err(|_| format!("bad numeric name component {i:?}"))?;
+                define(&mut self.names, index, Name::num(pre, i), "name")
+            }
+            "#US" => {
+                let level = Level::Succ(Box::new(self.level(fields.index()?)?));
+                define(&mut self.levels, index, level, "level")
+            }
+            "#UM" | "#UIM" => {
+                let l1 = Box::new(self.level(fields.index()?)?);
+                let l2 = Box::new(self.level(fields.index()?)?);
+                let level = if kind == "#UM" {
+                    Level::Max(l1, l2)
+                } else {
+                    Level::IMax(l1, l2)
+                };
+                define(&mut self.levels, index, level, "level")
+            }
+            "#UP" => {
+                let level = Level::Param(self.name(fields.index()?)?);
+                define(&mut self.levels, index, level, "level")
+            }
+            _ => {
+                let node = self.read_expr(kind, &mut fields)?;
+                let expr = self.arena.add(node);
+                define(&mut self.exprs, index, expr, "expression")
+            }
+        }
+    }
+
+    fn read_expr(&self, kind: &str, fields: &mut Fields) -> Result<ExprNode, String> {
+        Ok(match kind {
+            "#EV" => ExprNode::BVar {
+                de_bruijn_index: fields.index()? as u64,
+            },
+            "#ES" => ExprNode::Sort {
+                u: self.level(fields.index()?)?,
+            },
+            "#EC" => ExprNode::Const {
+                decl_name: self.name(fields.index()?)?,
+                us: fields
+                    .indices()?
+                    .into_iter()
+                    .map(|u| self.level(u))
+                    .collect::<Result<_, _>>()?,
+            },
+            "#EA" => ExprNode::App {
+                function: self.expr(fields.index()?)?,
+                arg: self.expr(fields.index()?)?,
+            },
+            "#EL" | "#EP" => {
+                let binder_info = binder_info(fields.next()?)?;
+                let binder_name = self.name(fields.index()?)?;
+                let binder_type = self.expr(fields.index()?)?;
+                let body = self.expr(fields.index()?)?;
+                if kind == "#EL" {
+                    ExprNode::Lam {
+                        binder_name,
+                        binder_type,
+                        body,
+                        binder_info,
+                    }
+                } else {
+                    ExprNode::ForallE {
+                        binder_name,
+                        binder_type,
+                        body,
+                        binder_info,
+                    }
+                }
+            }
+            "#EZ" => ExprNode::LetE {
+                decl_name: self.name(fields.index()?)?,
+                typ: self.expr(fields.index()?)?,
+                value: self.expr(fields.index()?)?,
+                body: self.expr(fields.index()?)?,
+                non_dep: false,
+            },
+            "#EJ" => ExprNode::Proj {
+                type_name: self.name(fields.index()?)?,
+                idx: fields.index()? as u64,
+                structure: self.expr(fields.index()?)?,
+            },
+            "#ELN" => {
+                let val = fields.next()?;
+                ExprNode::Lit(Literal::NatVal {
+                    val: val
+                        .parse::<BigInt>()
+                        .map_err(|_| format!("bad nat literal {val:?}"))?,
+                })
+            }
+            // string literals are exported as the hex bytes of their UTF-8 encoding
+            "#ELS" => {
+                let bytes = std::iter::from_fn(|| fields.0.next())
+                    .map(|byte| {
+                        u8::from_str_radix(byte, 16)
+                            .map_err(|_| format!("bad string byte {byte:?}"))
+                    })
+                    .collect::<Result<Vec<u8>, _>>()?;
+                ExprNode::Lit(Literal::StrVal {
+                    val: String::from_utf8(bytes)
+                        .map_err(|_| "string literal isn't valid UTF-8".to_string())?,
+                })
+            }
+            _ => return Err(format!("unknown entry kind {kind:?}")),
+        })
+    }
+
+    // read a `#<kind> ...` declaration, returning None for the kinds without a plain type/value
+    // (inductives, constructors, recursors and quotients)
+    fn read_declaration(
+        &self,
+        kind: &str,
+        fields: &mut Fields,
+    ) -> Result<Option<ExportedDeclaration>, String> {
+        let kind = match kind {
+            "#AX" => DeclarationKind::Axiom,
+            "#DEF" => DeclarationKind::Definition,
+            "#THM" => DeclarationKind::Theorem,
+            "#OPAQ" => DeclarationKind::Opaque,
+            _ => return Ok(None),
+        };
+        let name = self.name(fields.index()?)?;
+        let typ = self.expr(fields.index()?)?;
+        let value = match kind {
+            DeclarationKind::Axiom => None,
+            _ => Some(self.expr(fields.index()?)?),
+        };
+        // definitions' hints come before their universe parameters (which are indices, so they
+        // can't be mistaken for one)
+        let hints = match (kind, fields.0.peek()) {
+            (DeclarationKind::Definition, Some(&"O")) => Some(ReducibilityHints::Opaque),
+            (DeclarationKind::Definition, Some(&"A")) => Some(ReducibilityHints::Abbrev),
+            (DeclarationKind::Definition, Some(&"R")) => {
+                fields.next()?;
+                let height = fields.next()?;
+                Some(ReducibilityHints::Regular(
+                    height
+                        .parse()
+                        .map_err(|_| format!("bad definition height {height:?}"))?,
+                ))
+            }
+            _ => None,
+        };
+        if matches!(
+            hints,
+            Some(ReducibilityHints::Opaque | ReducibilityHints::Abbrev)
+        ) {
+            fields.next()?;
+        }
+        let level_params = fields
+            .indices()?
+            .into_iter()
+            .map(|n| self.name(n))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(ExportedDeclaration {
+            kind,
+            name,
+            level_params,
+            typ,
+            value,
+            hints,
+        }))
+    }
+}
+
+// read every axiom, definition, theorem and opaque declaration out of a lean4export text dump
+pub fn parse_export(input: &str) -> Result<Export, ExportParseError> {
+    let mut tables = ExportTables::new();
+    let mut declarations = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let error = |message| ExportParseError {
+            line: i + 1,
+            message,
+        };
+        let mut fields = Fields(line.split_whitespace().peekable());
+        let Some(first) = fields.0.next() else {
+            continue;
+        };
+
+        if first.starts_with('#') {
+            if let Some(declaration) = tables.read_declaration(first, &mut fields).map_err(error)? {
+                declarations.push(declaration);
+            }
+        } else if let Ok(index) = first.parse::<usize>() {
+            let kind = fields.next().map_err(error)?;
+            tables.read_entry(index, kind, line).map_err(error)?;
+        } else if i != 0 {
+            // the only line which isn't an entry or a declaration is the version header
+            return Err(error(format!("unexpected line {line:?}")));
+        }
+    }
+
+    Ok(Export {
+        arena: tables.arena,
+        declarations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lean_expr::{LeanExpr, Name};
+
+    use super::{parse_export, DeclarationKind, ReducibilityHints};
+
+    #[test]
+    fn test_parse_export() {
+        let export = parse_export(include_str!("../../test_assets/add_zero.export")).unwrap();
+        let declarations = &export.declarations;
+        let add_zero_json =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero.json"))
+                .unwrap();
+
+        // inductives are skipped, and everything else comes out in order
+        assert_eq!(
+            declarations
+                .iter()
+                .map(|d| (d.kind, d.name.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                (DeclarationKind::Axiom, "Nat".to_string()),
+                (DeclarationKind::Theorem, "Rat.add_zero".to_string()),
+                (
+                    DeclarationKind::Definition,
+                    "Rat.add_zero.proof_1".to_string()
+                ),
+            ]
+        );
+
+        // the export and the JSON describe the same theorem
+        assert_eq!(export.arena.to_expr(declarations[1].typ), add_zero_json);
+        assert_eq!(declarations[1].hints, None);
+        assert_eq!(declarations[2].hints, Some(ReducibilityHints::Regular(1)));
+        assert_eq!(declarations[2].level_params, vec![Name::from("u")]);
+
+        // the export's subterms are shared rather than copied into each expression using them
+        assert!(export.arena.len() <= 28);
+    }
+
+    #[test]
+    fn test_parse_export_hints() {
+        let tables = "1 #NS 0 u\n0 #ES 0\n";
+        for (hints, expected) in [
+            ("O", Some(ReducibilityHints::Opaque)),
+            ("A", Some(ReducibilityHints::Abbrev)),
+            ("R 4294967295", Some(ReducibilityHints::Regular(u32::MAX))),
+            // older lean4export versions don't write hints
+            ("", None),
+        ] {
+            let export = parse_export(&format!("{tables}#DEF 1 0 0 {hints} 1")).unwrap();
+
+            assert_eq!(export.declarations[0].hints, expected);
+            assert_eq!(export.declarations[0].level_params, vec![Name::from("u")]);
+        }
+
+        assert!(parse_export(&format!("{tables}#DEF 1 0 0 R x 1")).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_errors() {
+        let error = parse_export("1 #NS 0 Rat\n0 #EC 2").unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "reference to undefined name 2");
+
+        let error = parse_export("1 #NS 0 Rat\n3 #NS 1 add_zero").unwrap_err();
+
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_parse_export_literals() {
+        let export =
+            parse_export("0 #ELS 68 c3 a9\n1 #ELN 100000000000000000000\n#AX 0 0\n#AX 0 1")
+                .unwrap();
+        let typ = |i: usize| export.arena.to_expr(export.declarations[i].typ);
+
+        assert_eq!(typ(0).pretty(), r#""hé""#);
+        assert_eq!(typ(1).pretty(), "100000000000000000000");
+    }
+}
@@ -1,16 +1,42 @@
 #![allow(unused_variables)]
 
-use std::collections::HashMap;
 use std::fmt::Display;
 
 use display_tree::{write_tree, DisplayTree};
+use num::BigInt;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Literal {
-    NatVal { val: u64 },
-    StrVal { val: String },
+    NatVal {
+        #[serde(with = "nat_val")]
+        val: BigInt,
+    },
+    StrVal {
+        val: String,
+    },
+}
+
+// Lean exports Nat literals as JSON numbers of any size, which serde_json would round through an
+// f64 past u64::MAX, so they're read (and written) as the number's raw JSON text
+mod nat_val {
+    use num::{BigInt, BigUint};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::value::RawValue;
+
+    pub fn serialize<S: Serializer>(val: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+        RawValue::from_string(val.to_string())
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        raw.get().parse::<BigUint>().map(BigInt::from).map_err(|_| {
+            serde::de::Error::custom(format!("expected a natural number, found {}", raw.get()))
+        })
+    }
 }
 
 impl Display for Literal {
@@ -22,21 +48,219 @@ impl Display for Literal {
     }
 }
 
-pub type Name = String;
-// there seems to already be an ToJson instance for Name that makes it a string?
-/*
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub(crate) enum Name {
+// Lean's hierarchical names, which Lean's ToJson instance (and so exported.json) renders as their
+// dotted string form
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Name {
+    #[default]
     Anonymous,
-    Str { pre: Box<Name>, str: String },
-    Num { pre: Box<Name>, i: u32 },
+    Str {
+        pre: Box<Name>,
+        str: String,
+    },
+    Num {
+        pre: Box<Name>,
+        i: u64,
+    },
+}
+
+impl Name {
+    pub fn str(pre: Name, str: impl Into<String>) -> Self {
+        Name::Str {
+            pre: Box::new(pre),
+            str: str.into(),
+        }
+    }
+
+    pub fn num(pre: Name, i: u64) -> Self {
+        Name::Num {
+            pre: Box::new(pre),
+            i,
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        matches!(self, Name::Anonymous)
+    }
+
+    // the name without its last component (`Real.sqrt` for `Real.sqrt_two`)
+    pub fn prefix(&self) -> &Name {
+        match self {
+            Name::Anonymous => self,
+            Name::Str { pre, .. } | Name::Num { pre, .. } => pre,
+        }
+    }
+
+    // the last component, if it's a string
+    pub fn last_string(&self) -> Option<&str> {
+        match self {
+            Name::Str { str, .. } => Some(str),
+            _ => None,
+        }
+    }
+
+    pub fn num_components(&self) -> usize {
+        match self {
+            Name::Anonymous => 0,
+            Name::Str { pre, .. } | Name::Num { pre, .. } => pre.num_components() + 1,
+        }
+    }
+
+    // whether this name is a (not necessarily strict) namespace prefix of other, so `Real` is a
+    // prefix of `Real.sqrt` but not of `RealSqrt`
+    pub fn is_prefix_of(&self, other: &Name) -> bool {
+        let extra = match other.num_components().checked_sub(self.num_components()) {
+            Some(extra) => extra,
+            None => return false,
+        };
+        (0..extra).fold(other, |name, _| name.prefix()) == self
+    }
+
+    // this name with each component of other appended to it
+    pub fn append(&self, other: &Name) -> Name {
+        match other {
+            Name::Anonymous => self.clone(),
+            Name::Str { pre, str } => Name::str(self.append(pre), str.clone()),
+            Name::Num { pre, i } => Name::num(self.append(pre), *i),
+        }
+    }
+
+    // a string component needs «escaping» if it wouldn't parse back as itself
+    fn needs_escape(str: &str) -> bool {
+        str.is_empty()
+            || str.starts_with(|c: char| c.is_ascii_digit())
+            || str.contains(|c: char| c == '.' || c == '«' || c == '»' || c.is_whitespace())
+    }
+}
+
+impl Ord for Name {
+    // Lean's Name.cmp: names ending in a number sort before names ending in a string, and
+    // otherwise prefixes are compared before last components
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        match (self, other) {
+            (Name::Anonymous, Name::Anonymous) => Ordering::Equal,
+            (Name::Anonymous, _) => Ordering::Less,
+            (_, Name::Anonymous) => Ordering::Greater,
+            (Name::Num { pre: p1, i: i1 }, Name::Num { pre: p2, i: i2 }) => {
+                p1.cmp(p2).then(i1.cmp(i2))
+            }
+            (Name::Num { .. }, Name::Str { .. }) => Ordering::Less,
+            (Name::Str { .. }, Name::Num { .. }) => Ordering::Greater,
+            (Name::Str { pre: p1, str: s1 }, Name::Str { pre: p2, str: s2 }) => {
+                p1.cmp(p2).then(s1.cmp(s2))
+            }
+        }
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Name::Anonymous => write!(f, "[anonymous]"),
+            Name::Str { pre, str } => {
+                if !pre.is_anonymous() {
+                    write!(f, "{pre}.")?;
+                }
+                if Name::needs_escape(str) {
+                    write!(f, "«{str}»")
+                } else {
+                    write!(f, "{str}")
+                }
+            }
+            Name::Num { pre, i } => {
+                if !pre.is_anonymous() {
+                    write!(f, "{pre}.")?;
+                }
+                write!(f, "{i}")
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Name {
+    type Err = std::convert::Infallible;
+
+    // the inverse of Display: splits on dots outside of «escapes», with all-digit components
+    // becoming numeric
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "[anonymous]" || s.is_empty() {
+            return Ok(Name::Anonymous);
+        }
+
+        let mut name = Name::Anonymous;
+        let mut component = String::new();
+        let mut escaped = false;
+        let mut in_escape = false;
+        let push = |name: &mut Name, component: &mut String, escaped: bool| {
+            let pre = std::mem::take(name);
+            *name = match component.parse::<u64>() {
+                Ok(i) if !escaped => Name::num(pre, i),
+                _ => Name::str(pre, std::mem::take(component)),
+            };
+            component.clear();
+        };
+        for c in s.chars() {
+            match c {
+                '«' if !in_escape => {
+                    in_escape = true;
+                    escaped = true;
+                }
+                '»' if in_escape => in_escape = false,
+                '.' if !in_escape => {
+                    push(&mut name, &mut component, escaped);
+                    escaped = false;
+                }
+                c => component.push(c),
+            }
+        }
+        push(&mut name, &mut component, escaped);
+
+        Ok(name)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(s: &str) -> Self {
+        s.parse().expect("name parsing is infallible")
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        format!("{self}") == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        format!("{self}") == *other
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Name::from(s.as_str()))
+    }
 }
-*/
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct LMVarId {
-    name: Name,
+    pub name: Name,
 }
 
 impl Display for LMVarId {
@@ -47,7 +271,7 @@ impl Display for LMVarId {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FVarId {
-    name: Name,
+    pub name: Name,
 }
 
 impl Display for FVarId {
@@ -58,7 +282,7 @@ impl Display for FVarId {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MVarId {
-    name: Name,
+    pub name: Name,
 }
 
 impl Display for MVarId {
@@ -108,28 +332,164 @@ impl Display for BinderInfo {
     }
 }
 
+// a byte offset into a source file (Lean's String.Pos)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StringPos {
+    pub byte_idx: u64,
+}
+
+// a slice of a string by byte offsets, which Lean exports along with the whole string
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-// hell no.
+pub struct Substring {
+    pub str: String,
+    pub start_pos: StringPos,
+    pub stop_pos: StringPos,
+}
+
+impl Substring {
+    // the text this substring refers to (empty if its positions aren't valid for its string)
+    pub fn as_str(&self) -> &str {
+        self.str
+            .get(self.start_pos.byte_idx as usize..self.stop_pos.byte_idx as usize)
+            .unwrap_or_default()
+    }
+}
+
+impl Display for Substring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(rename_all_fields = "camelCase")]
+pub enum SourceInfo {
+    // parsed from source, with the whitespace and comments around it
+    Original {
+        leading: Substring,
+        pos: StringPos,
+        trailing: Substring,
+        end_pos: StringPos,
+    },
+    // generated, but attributed to a range of the source
+    Synthetic {
+        pos: StringPos,
+        end_pos: StringPos,
+        canonical: bool,
+    },
+    None,
+}
+
+impl SourceInfo {
+    // the range of source this info is attributed to, if any
+    pub fn range(&self) -> Option<(StringPos, StringPos)> {
+        match self {
+            SourceInfo::Original { pos, end_pos, .. }
+            | SourceInfo::Synthetic { pos, end_pos, .. } => Some((*pos, *end_pos)),
+            SourceInfo::None => None,
+        }
+    }
+}
+
+// the global declarations an identifier may have been resolved to at parse time
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Preresolved {
+    Namespace { ns: Name },
+    Decl { n: Name, fields: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(rename_all_fields = "camelCase")]
 pub enum Syntax {
     Missing,
-    Node {},
-    Atom {},
-    Ident {},
+    Node {
+        info: SourceInfo,
+        kind: Name,
+        args: Vec<Syntax>,
+    },
+    Atom {
+        info: SourceInfo,
+        val: String,
+    },
+    Ident {
+        info: SourceInfo,
+        raw_val: Substring,
+        val: Name,
+        preresolved: Vec<Preresolved>,
+    },
+}
+
+impl Syntax {
+    pub fn info(&self) -> Option<&SourceInfo> {
+        match self {
+            Syntax::Missing => None,
+            Syntax::Node { info, .. } | Syntax::Atom { info, .. } | Syntax::Ident { info, .. } => {
+                Some(info)
+            }
+        }
+    }
+
+    // the range of source this syntax covers: from the first position in it to the last
+    pub fn range(&self) -> Option<(StringPos, StringPos)> {
+        let own = self.info().and_then(SourceInfo::range);
+        let children = match self {
+            Syntax::Node { args, .. } => args.iter().filter_map(Syntax::range).collect(),
+            _ => Vec::new(),
+        };
+
+        own.into_iter()
+            .chain(children)
+            .reduce(|(start1, end1), (start2, end2)| (start1.min(start2), end1.max(end2)))
+    }
+
+    // the source text of this syntax, as originally written (Lean's Syntax.reprint, without the
+    // surrounding whitespace)
+    pub fn reprint(&self) -> String {
+        let mut out = String::new();
+        self.reprint_into(&mut out);
+        out.trim().to_string()
+    }
+
+    fn reprint_into(&self, out: &mut String) {
+        let (info, val) = match self {
+            Syntax::Missing => return,
+            Syntax::Node { args, .. } => {
+                for arg in args {
+                    arg.reprint_into(out);
+                }
+                return;
+            }
+            Syntax::Atom { info, val } => (info, val.clone()),
+            Syntax::Ident { info, raw_val, .. } => (info, raw_val.as_str().to_string()),
+        };
+        match info {
+            SourceInfo::Original {
+                leading, trailing, ..
+            } => {
+                out.push_str(leading.as_str());
+                out.push_str(&val);
+                out.push_str(trailing.as_str());
+            }
+            _ => {
+                // synthesized syntax has no whitespace of its own, so separate it from its
+                // neighbours
+                if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+                    out.push(' ');
+                }
+                out.push_str(&val);
+            }
+        }
+    }
 }
 
 impl Display for Syntax {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Syntax::{}",
-            match self {
-                Syntax::Missing => "Missing",
-                Syntax::Ident {} => "Ident",
-                Syntax::Atom {} => "Atom",
-                Syntax::Node {} => "Node",
-            }
-        )
+        write!(f, "{}", self.reprint())
     }
 }
 
@@ -150,9 +510,10 @@ impl Display for DataValue {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct MData(HashMap<Name, DataValue>);
+// Lean exports MData as its list of entries rather than as an object
+pub struct MData(pub Vec<(Name, DataValue)>);
 
 impl Display for MData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -160,7 +521,7 @@ impl Display for MData {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, DisplayTree)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, DisplayTree)]
 #[serde(rename = "Expr")]
 #[serde(rename_all = "lowercase")]
 #[serde(rename_all_fields = "camelCase")]
@@ -242,8 +603,9 @@ impl Display for LeanExpr {
 #[cfg(test)]
 mod tests {
     use display_tree::println_tree;
+    use num::BigInt;
 
-    use super::LeanExpr;
+    use super::{DataValue, LeanExpr, Literal, MData, Name, StringPos, Syntax};
 
     #[test]
     fn x() {
@@ -314,10 +676,146 @@ mod tests {
   "binderType": {"const": {"us": [], "declName": "Rat"}},
   "binderName": "a",
   "binderInfo": "default"}}"#;
-        let obj: LeanExpr = serde_json::from_str(&json).unwrap();
+        let obj: LeanExpr = serde_json::from_str(json).unwrap();
         println!("{:?}", obj);
         println!("{}", serde_json::to_string(&obj).unwrap());
 
         println_tree!(obj);
     }
+
+    #[test]
+    fn test_name() {
+        let name = Name::from("Real.sqrt_two_mul_self");
+
+        assert_eq!(
+            name,
+            Name::str(Name::str(Name::Anonymous, "Real"), "sqrt_two_mul_self")
+        );
+        assert_eq!(name.to_string(), "Real.sqrt_two_mul_self");
+        assert_eq!(name.prefix(), &Name::from("Real"));
+        assert_eq!(name.last_string(), Some("sqrt_two_mul_self"));
+        assert!(Name::from("Real").is_prefix_of(&name));
+        assert!(!Name::from("Re").is_prefix_of(&name));
+        assert!(!name.is_prefix_of(&Name::from("Real")));
+        assert_eq!(name, "Real.sqrt_two_mul_self");
+
+        // numeric and escaped components survive a round trip through the string form
+        let private = Name::from("_private.Mathlib.0.«a.b».c");
+
+        assert_eq!(
+            private,
+            Name::str(
+                Name::str(
+                    Name::num(
+                        Name::str(Name::str(Name::Anonymous, "_private"), "Mathlib"),
+                        0
+                    ),
+                    "a.b"
+                ),
+                "c"
+            )
+        );
+        assert_eq!(private.to_string(), "_private.Mathlib.0.«a.b».c");
+        assert_eq!(Name::from("[anonymous]"), Name::Anonymous);
+
+        // Lean's ordering: numeric components first, then prefixes before last components
+        let mut names = ["x.a", "Real.add_zero", "x.1", "Rat.add_zero", "Real"].map(Name::from);
+        names.sort();
+
+        assert_eq!(
+            names,
+            ["x.1", "Real", "Rat.add_zero", "Real.add_zero", "x.a"].map(Name::from)
+        );
+    }
+
+    #[test]
+    fn test_name_json() {
+        let json = r#"{"const": {"us": [], "declName": "Real.pi"}}"#;
+        let obj: LeanExpr = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            obj,
+            LeanExpr::Const {
+                decl_name: Name::str(Name::str(Name::Anonymous, "Real"), "pi"),
+                us: Vec::new(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&obj).unwrap(),
+            r#"{"const":{"declName":"Real.pi","us":[]}}"#
+        );
+    }
+
+    #[test]
+    fn test_syntax() {
+        // `a + 0`, as parsed from a file containing just that
+        let json = r#"{"node": {"info": "none", "kind": "«term_+_»", "args": [
+            {"ident": {
+                "info": {"original": {
+                    "leading": {"str": "a + 0", "startPos": {"byteIdx": 0}, "stopPos": {"byteIdx": 0}},
+                    "pos": {"byteIdx": 0},
+                    "trailing": {"str": "a + 0", "startPos": {"byteIdx": 1}, "stopPos": {"byteIdx": 2}},
+                    "endPos": {"byteIdx": 1}}},
+                "rawVal": {"str": "a + 0", "startPos": {"byteIdx": 0}, "stopPos": {"byteIdx": 1}},
+                "val": "a",
+                "preresolved": [{"decl": {"n": "Foo.a", "fields": []}}]}},
+            {"atom": {
+                "info": {"original": {
+                    "leading": {"str": "a + 0", "startPos": {"byteIdx": 2}, "stopPos": {"byteIdx": 2}},
+                    "pos": {"byteIdx": 2},
+                    "trailing": {"str": "a + 0", "startPos": {"byteIdx": 3}, "stopPos": {"byteIdx": 4}},
+                    "endPos": {"byteIdx": 3}}},
+                "val": "+"}},
+            {"node": {"info": "none", "kind": "num", "args": [
+                {"atom": {
+                    "info": {"synthetic": {"pos": {"byteIdx": 4}, "endPos": {"byteIdx": 5}, "canonical": false}},
+                    "val": "0"}}]}}]}}"#;
+        let syntax: Syntax = serde_json::from_str(json).unwrap();
+
+        assert_eq!(syntax.reprint(), "a + 0");
+        assert_eq!(
+            syntax.range(),
+            Some((StringPos { byte_idx: 0 }, StringPos { byte_idx: 5 }))
+        );
+        assert_eq!(
+            serde_json::from_str::<Syntax>(&serde_json::to_string(&syntax).unwrap()).unwrap(),
+            syntax
+        );
+
+        // syntax attached to an expression as metadata gives that expression a source position
+        let expr = LeanExpr::MData {
+            data: MData(vec![("syntax".into(), DataValue::OfSyntax { v: syntax })]),
+            expr: Box::new(LeanExpr::BVar { de_bruijn_index: 0 }),
+        };
+
+        assert_eq!(
+            expr.source_range(),
+            Some((StringPos { byte_idx: 0 }, StringPos { byte_idx: 5 }))
+        );
+    }
+
+    #[test]
+    fn test_big_nat_literal() {
+        let json = r#"{"lit": {"natVal": {"val": 1267650600228229401496703205376}}}"#;
+        let obj: LeanExpr = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            obj,
+            LeanExpr::Lit(Literal::NatVal {
+                val: BigInt::from(2).pow(100)
+            })
+        );
+        assert_eq!(
+            serde_json::to_string(&obj).unwrap(),
+            r#"{"lit":{"natVal":{"val":1267650600228229401496703205376}}}"#
+        );
+
+        for json in [
+            r#"{"lit": {"natVal": {"val": -1}}}"#,
+            r#"{"lit": {"natVal": {"val": 1.5}}}"#,
+            r#"{"lit": {"natVal": {"val": "1"}}}"#,
+        ] {
+            assert!(serde_json::from_str::<LeanExpr>(json).is_err());
+        }
+    }
 }
@@ -0,0 +1,311 @@
+use std::cmp::Ordering;
+
+use crate::lean_expr::{Level, Name};
+
+impl Level {
+    pub fn succ(l: Level) -> Level {
+        Level::Succ(Box::new(l))
+    }
+
+    pub fn max(l1: Level, l2: Level) -> Level {
+        Level::Max(Box::new(l1), Box::new(l2))
+    }
+
+    pub fn imax(l1: Level, l2: Level) -> Level {
+        Level::IMax(Box::new(l1), Box::new(l2))
+    }
+
+    // the explicit level n (0 + 1 + ... + 1)
+    pub fn of_nat(n: u64) -> Level {
+        Level::Zero.add_offset(n)
+    }
+
+    pub fn add_offset(self, k: u64) -> Level {
+        (0..k).fold(self, |l, _| Level::succ(l))
+    }
+
+    // split a level into the level underneath its successors and the number of successors
+    pub fn to_offset(&self) -> (&Level, u64) {
+        let mut l = self;
+        let mut k = 0;
+        while let Level::Succ(u) = l {
+            l = u;
+            k += 1;
+        }
+        (l, k)
+    }
+
+    // Some(n) if this is the explicit level n
+    pub fn to_nat(&self) -> Option<u64> {
+        match self.to_offset() {
+            (Level::Zero, k) => Some(k),
+            _ => None,
+        }
+    }
+
+    // whether this level is nonzero whatever its parameters are instantiated to
+    pub fn is_never_zero(&self) -> bool {
+        match self {
+            Level::Zero | Level::Param(_) | Level::MVar(_) => false,
+            Level::Succ(_) => true,
+            Level::Max(l1, l2) => l1.is_never_zero() || l2.is_never_zero(),
+            Level::IMax(_, l2) => l2.is_never_zero(),
+        }
+    }
+
+    pub fn has_param(&self) -> bool {
+        match self {
+            Level::Zero | Level::MVar(_) => false,
+            Level::Param(_) => true,
+            Level::Succ(l) => l.has_param(),
+            Level::Max(l1, l2) | Level::IMax(l1, l2) => l1.has_param() || l2.has_param(),
+        }
+    }
+
+    // replace each universe parameter params[i] by levels[i]
+    pub fn instantiate_params(&self, params: &[Name], levels: &[Level]) -> Level {
+        match self {
+            Level::Zero | Level::MVar(_) => self.clone(),
+            Level::Param(name) => params
+                .iter()
+                .zip(levels)
+                .find(|(param, _)| *param == name)
+                .map_or_else(|| self.clone(), |(_, level)| level.clone()),
+            Level::Succ(l) => Level::succ(l.instantiate_params(params, levels)),
+            Level::Max(l1, l2) => Level::max(
+                l1.instantiate_params(params, levels),
+                l2.instantiate_params(params, levels),
+            ),
+            Level::IMax(l1, l2) => Level::imax(
+                l1.instantiate_params(params, levels),
+                l2.instantiate_params(params, levels),
+            ),
+        }
+    }
+
+    // the operands of a (normalized) nested max, each split into its base and offset, with the
+    // offset k distributed over them
+    fn push_max_args<'a>(&'a self, k: u64, args: &mut Vec<(&'a Level, u64)>) {
+        let (base, offset) = self.to_offset();
+        match base {
+            Level::Max(l1, l2) => {
+                l1.push_max_args(k + offset, args);
+                l2.push_max_args(k + offset, args);
+            }
+            _ => args.push((base, k + offset)),
+        }
+    }
+
+    // bring a level into a normal form, such that equivalent levels usually normalize equally:
+    // successors are pushed into maxes, nested maxes are flattened, sorted and deduplicated, and
+    // imaxes are simplified where their right operand is known to be zero or nonzero
+    pub fn normalize(&self) -> Level {
+        let (base, k) = self.to_offset();
+        match base {
+            Level::Zero | Level::Param(_) | Level::MVar(_) => self.clone(),
+            Level::IMax(l1, l2) if l2.is_never_zero() => Level::max((**l1).clone(), (**l2).clone())
+                .normalize()
+                .add_offset(k),
+            Level::IMax(l1, l2) => {
+                let l1 = l1.normalize();
+                let l2 = l2.normalize();
+                let imax = if l2 == Level::Zero {
+                    Level::Zero
+                } else if l1 == Level::Zero || l1 == Level::of_nat(1) || l1 == l2 {
+                    l2
+                } else {
+                    Level::imax(l1, l2)
+                };
+                imax.add_offset(k)
+            }
+            Level::Max(l1, l2) => {
+                let l1 = l1.normalize();
+                let l2 = l2.normalize();
+                let mut args = Vec::new();
+                l1.push_max_args(k, &mut args);
+                l2.push_max_args(k, &mut args);
+
+                // only the largest offset of each base matters
+                args.sort_by(|(b1, k1), (b2, k2)| norm_cmp(b1, b2).then(k2.cmp(k1)));
+                args.dedup_by(|(b2, _), (b1, _)| b1 == b2);
+                // and an explicit level is subsumed by any other operand with at least its offset
+                if let Some((Level::Zero, explicit)) = args.first() {
+                    let explicit = *explicit;
+                    if args[1..].iter().any(|(_, k)| *k >= explicit) {
+                        args.remove(0);
+                    }
+                }
+
+                args.into_iter()
+                    .rev()
+                    .map(|(base, k)| base.clone().add_offset(k))
+                    .reduce(|max, l| Level::max(l, max))
+                    .expect("a max has operands")
+            }
+            Level::Succ(_) => unreachable!("to_offset strips successors"),
+        }
+    }
+
+    // whether the levels are equal for every instantiation of their parameters (as far as
+    // normalization can tell)
+    pub fn is_equiv(&self, other: &Level) -> bool {
+        self == other || self.normalize() == other.normalize()
+    }
+
+    // whether this level is at least other for every instantiation of their parameters. This is
+    // sound but, like Lean's check, not complete
+    pub fn geq(&self, other: &Level) -> bool {
+        self.normalize().geq_core(&other.normalize())
+    }
+
+    fn geq_core(&self, other: &Level) -> bool {
+        if *other == Level::Zero || self == other {
+            return true;
+        }
+        match (self, other) {
+            (_, Level::Max(l1, l2)) => self.geq_core(l1) && self.geq_core(l2),
+            (Level::Max(l1, l2), _) if l1.geq_core(other) || l2.geq_core(other) => true,
+            // imax l1 l2 ≤ max l1 l2
+            (_, Level::IMax(l1, l2)) => self.geq_core(l1) && self.geq_core(l2),
+            // imax l1 l2 ≥ l2
+            (Level::IMax(_, l2), _) => l2.geq_core(other),
+            _ => {
+                let (b1, k1) = self.to_offset();
+                let (b2, k2) = other.to_offset();
+                if b1 == b2 || *b2 == Level::Zero {
+                    k1 >= k2
+                } else if k1 == k2 && k1 > 0 {
+                    b1.geq_core(b2)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    // print this level the way Lean does, e.g. `max u (v+1)`
+    pub fn pretty(&self) -> String {
+        self.pretty_prec(false)
+    }
+
+    // atomic levels are printed without parentheses even as an argument
+    fn pretty_prec(&self, argument: bool) -> String {
+        let paren = |s: String| if argument { format!("({s})") } else { s };
+        match self.to_offset() {
+            (Level::Zero, k) => k.to_string(),
+            (Level::Param(name), 0) => name.to_string(),
+            (Level::MVar(mvar), 0) => format!("?{}", mvar.name),
+            (Level::Max(l1, l2), 0) => paren(format!(
+                "max {} {}",
+                l1.pretty_prec(true),
+                l2.pretty_prec(true)
+            )),
+            (Level::IMax(l1, l2), 0) => paren(format!(
+                "imax {} {}",
+                l1.pretty_prec(true),
+                l2.pretty_prec(true)
+            )),
+            (base, k) => paren(format!("{}+{k}", base.pretty_prec(true))),
+        }
+    }
+}
+
+// the order operands of a normalized max are sorted into: explicit levels first, then
+// parameters, metavariables, maxes and imaxes
+fn norm_cmp(l1: &Level, l2: &Level) -> Ordering {
+    fn rank(l: &Level) -> u8 {
+        match l {
+            Level::Zero => 0,
+            Level::Param(_) => 1,
+            Level::MVar(_) => 2,
+            Level::Succ(_) => 3,
+            Level::Max(_, _) => 4,
+            Level::IMax(_, _) => 5,
+        }
+    }
+
+    rank(l1).cmp(&rank(l2)).then_with(|| match (l1, l2) {
+        (Level::Param(n1), Level::Param(n2)) => n1.cmp(n2),
+        (Level::MVar(m1), Level::MVar(m2)) => m1.name.cmp(&m2.name),
+        _ => l1.pretty().cmp(&l2.pretty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lean_expr::Level;
+
+    fn param(name: &str) -> Level {
+        Level::Param(name.into())
+    }
+
+    #[test]
+    fn test_normalize() {
+        let u = param("u");
+        let v = param("v");
+
+        // succ (max v u) = max (u+1) (v+1)
+        assert_eq!(
+            Level::succ(Level::max(v.clone(), u.clone())).normalize(),
+            Level::max(Level::succ(u.clone()), Level::succ(v.clone()))
+        );
+        // max (max u v) u = max u v
+        assert_eq!(
+            Level::max(Level::max(u.clone(), v.clone()), u.clone()).normalize(),
+            Level::max(u.clone(), v.clone())
+        );
+        // max 1 (u+1) = u+1, but max 2 (u+1) stays
+        assert_eq!(
+            Level::max(Level::of_nat(1), Level::succ(u.clone())).normalize(),
+            Level::succ(u.clone())
+        );
+        assert_eq!(
+            Level::max(Level::succ(u.clone()), Level::of_nat(2)).normalize(),
+            Level::max(Level::of_nat(2), Level::succ(u.clone()))
+        );
+        // imax u 0 = 0, imax u (v+1) = max u (v+1), imax 1 v = v
+        assert_eq!(Level::imax(u.clone(), Level::Zero).normalize(), Level::Zero);
+        assert_eq!(
+            Level::imax(u.clone(), Level::succ(v.clone())).normalize(),
+            Level::max(u.clone(), Level::succ(v.clone()))
+        );
+        assert_eq!(Level::imax(Level::of_nat(1), v.clone()).normalize(), v);
+        assert_eq!(
+            Level::imax(u.clone(), v.clone()).normalize(),
+            Level::imax(u, v)
+        );
+    }
+
+    #[test]
+    fn test_geq() {
+        let u = param("u");
+        let v = param("v");
+        let max_uv1 = Level::max(u.clone(), Level::succ(v.clone()));
+
+        assert!(max_uv1.geq(&u));
+        assert!(max_uv1.geq(&v));
+        assert!(max_uv1.geq(&Level::of_nat(1)));
+        assert!(!max_uv1.geq(&Level::of_nat(2)));
+        assert!(!u.geq(&v));
+        assert!(Level::succ(u.clone()).geq(&u));
+        assert!(!u.geq(&Level::succ(u.clone())));
+        assert!(Level::imax(v.clone(), u.clone()).geq(&u));
+        assert!(max_uv1.is_equiv(&Level::max(Level::succ(v), u)));
+    }
+
+    #[test]
+    fn test_instantiate_and_pretty() {
+        let level = Level::max(param("u"), Level::succ(param("v")));
+
+        assert_eq!(level.pretty(), "max u (v+1)");
+        assert_eq!(
+            Level::imax(Level::of_nat(2), level.clone()).pretty(),
+            "imax 2 (max u (v+1))"
+        );
+
+        let instantiated =
+            level.instantiate_params(&["u".into(), "v".into()], &[Level::Zero, param("w")]);
+        assert_eq!(instantiated.pretty(), "max 0 (w+1)");
+        assert_eq!(instantiated.normalize().pretty(), "w+1");
+    }
+}
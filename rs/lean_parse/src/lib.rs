@@ -1 +1,6 @@
-pub mod lean_expr;
\ No newline at end of file
+pub mod expr_arena;
+mod expr_ops;
+pub mod lean4export;
+pub mod lean_expr;
+mod level;
+mod pretty;
\ No newline at end of file
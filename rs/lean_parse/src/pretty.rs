@@ -0,0 +1,542 @@
+use crate::lean_expr::{BinderInfo, LeanExpr, Level, Literal, Name};
+
+// precedences, matching Lean's own notation declarations
+const MAX_PREC: u32 = 1024;
+const ARG_PREC: u32 = MAX_PREC + 1;
+const ATOM_PREC: u32 = u32::MAX;
+
+// a rendered subexpression along with the precedence it was rendered at
+struct Rendered {
+    text: String,
+    prec: u32,
+}
+
+impl Rendered {
+    fn new(text: String, prec: u32) -> Self {
+        Self { text, prec }
+    }
+
+    fn atom(text: String) -> Self {
+        Self::new(text, ATOM_PREC)
+    }
+
+    // the text of this subexpression, parenthesized if it binds looser than required
+    fn at(self, required: u32) -> String {
+        if self.prec < required {
+            format!("({})", self.text)
+        } else {
+            self.text
+        }
+    }
+}
+
+// the notation Lean (or Mathlib) uses for a constant, where it has one
+fn constant_notation(name: &str) -> Option<&'static str> {
+    match name {
+        "Nat" => Some("ℕ"),
+        "Int" => Some("ℤ"),
+        "Rat" => Some("ℚ"),
+        "Real" => Some("ℝ"),
+        "Real.pi" => Some("π"),
+        "True" => Some("True"),
+        "False" => Some("False"),
+        _ => None,
+    }
+}
+
+// infix operators, as (number of leading implicit/instance arguments, symbol, precedence, left
+// argument precedence, right argument precedence)
+fn infix_notation(name: &str) -> Option<(usize, &'static str, u32, u32, u32)> {
+    match name {
+        "HAdd.hAdd" => Some((4, "+", 65, 65, 66)),
+        "HSub.hSub" => Some((4, "-", 65, 65, 66)),
+        "HMul.hMul" => Some((4, "*", 70, 70, 71)),
+        "HDiv.hDiv" => Some((4, "/", 70, 70, 71)),
+        "HPow.hPow" => Some((4, "^", 75, 76, 75)),
+        "Eq" => Some((1, "=", 50, 51, 51)),
+        "Ne" => Some((1, "≠", 50, 51, 51)),
+        "LT.lt" => Some((2, "<", 50, 51, 51)),
+        "LE.le" => Some((2, "≤", 50, 51, 51)),
+        "And" => Some((0, "∧", 35, 36, 35)),
+        "Or" => Some((0, "∨", 30, 31, 30)),
+        "Iff" => Some((0, "↔", 20, 21, 21)),
+        _ => None,
+    }
+}
+
+// prefix and postfix operators, as (number of leading implicit/instance arguments, symbol,
+// whether it's postfix, precedence, argument precedence)
+fn unary_notation(name: &str) -> Option<(usize, &'static str, bool, u32, u32)> {
+    match name {
+        "Neg.neg" => Some((2, "-", false, 75, 75)),
+        "Inv.inv" => Some((2, "⁻¹", true, MAX_PREC, MAX_PREC)),
+        "Not" => Some((0, "¬", false, MAX_PREC, 40)),
+        "Nat.cast" | "Int.cast" | "Rat.cast" => Some((2, "↑", false, MAX_PREC, MAX_PREC)),
+        _ => None,
+    }
+}
+
+// whether a name is a structure's parent projection (`DivInvMonoid.toDiv`)
+fn is_parent_projection(last: &str) -> bool {
+    last.strip_prefix("to")
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(char::is_uppercase)
+}
+
+// Sort (l+1) is written Type l
+fn level_to_sort(u: &Level) -> String {
+    let (sort, level) = match u {
+        Level::Zero => return "Prop".to_string(),
+        Level::Succ(l) if **l == Level::Zero => return "Type".to_string(),
+        Level::Succ(l) => ("Type", l.pretty()),
+        l => ("Sort", l.pretty()),
+    };
+    if level.contains([' ', '+']) {
+        format!("{sort} ({level})")
+    } else {
+        format!("{sort} {level}")
+    }
+}
+
+fn binder_brackets(binder_info: &BinderInfo) -> (&'static str, &'static str) {
+    match binder_info {
+        BinderInfo::Default => ("(", ")"),
+        BinderInfo::Implicit => ("{", "}"),
+        BinderInfo::StrictImplicit => ("⦃", "⦄"),
+        BinderInfo::InstImplicit => ("[", "]"),
+    }
+}
+
+// Lean's rendering of OfScientific literals (mantissa * 10^(-exponent) if the sign is set)
+fn scientific(mantissa: &LeanExpr, sign: &LeanExpr, exponent: &LeanExpr) -> Option<String> {
+    let (
+        LeanExpr::Lit(Literal::NatVal { val: mantissa }),
+        LeanExpr::Const {
+            decl_name: sign, ..
+        },
+        LeanExpr::Lit(Literal::NatVal { val: exponent }),
+    ) = (mantissa, sign, exponent)
+    else {
+        return None;
+    };
+    let exponent = usize::try_from(exponent).ok()?;
+
+    match sign.to_string().as_str() {
+        "Bool.true" => {
+            let digits = format!("{mantissa:0>width$}", width = exponent + 1);
+            let (whole, fraction) = digits.split_at(digits.len() - exponent);
+            Some(format!("{whole}.{fraction}"))
+        }
+        "Bool.false" => Some(format!("{mantissa}e{exponent}")),
+        _ => None,
+    }
+}
+
+// the names of the binders currently in scope, innermost last, along with whether each is an
+// instance binder
+struct Printer {
+    names: Vec<String>,
+    instances: Vec<bool>,
+}
+
+impl Printer {
+    // a name for a new binder which doesn't shadow anything in scope
+    fn fresh(&self, name: &Name) -> String {
+        let base = if name.is_anonymous() {
+            "x".to_string()
+        } else {
+            name.to_string()
+        };
+        if !self.names.contains(&base) {
+            return base;
+        }
+        (1..)
+            .map(|i| format!("{base}_{i}"))
+            .find(|candidate| !self.names.contains(candidate))
+            .expect("some suffix is unused")
+    }
+
+    fn push_binder(&mut self, name: String, binder_info: &BinderInfo) {
+        self.names.push(name);
+        self.instances
+            .push(*binder_info == BinderInfo::InstImplicit);
+    }
+
+    fn pop_binders(&mut self, count: usize) {
+        self.names.truncate(self.names.len() - count);
+        self.instances.truncate(self.instances.len() - count);
+    }
+
+    fn with_binder<T>(
+        &mut self,
+        name: String,
+        binder_info: &BinderInfo,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.push_binder(name, binder_info);
+        let result = f(self);
+        self.pop_binders(1);
+        result
+    }
+
+    // whether an argument is an instance, in which case it's elided from ordinary applications.
+    // Without the function's type we can't see which of its binders are instance implicit, so this
+    // goes by the argument: a variable bound as an instance, a constant following Lean's `inst`
+    // naming convention for instances, or a parent projection of an instance (`DivInvMonoid.toDiv
+    // ℚ inst`). Implicit arguments that aren't instances (such as type arguments) are still printed
+    fn is_instance(&self, arg: &LeanExpr) -> bool {
+        match arg.get_app_fn() {
+            LeanExpr::BVar { de_bruijn_index } => self
+                .instances
+                .len()
+                .checked_sub(1 + *de_bruijn_index as usize)
+                .is_some_and(|i| self.instances[i]),
+            LeanExpr::Const { decl_name, .. } => decl_name.last_string().is_some_and(|last| {
+                last.starts_with("inst")
+                    || is_parent_projection(last)
+                        && arg
+                            .get_app_args()
+                            .last()
+                            .is_some_and(|parent| self.is_instance(parent))
+            }),
+            _ => false,
+        }
+    }
+
+    fn render(&mut self, expr: &LeanExpr) -> Rendered {
+        match expr {
+            LeanExpr::BVar { de_bruijn_index } => Rendered::atom(
+                self.names
+                    .len()
+                    .checked_sub(1 + *de_bruijn_index as usize)
+                    .map(|i| self.names[i].clone())
+                    .unwrap_or_else(|| format!("#{de_bruijn_index}")),
+            ),
+            LeanExpr::FVar { fvar_id } => Rendered::atom(fvar_id.name.to_string()),
+            LeanExpr::MVar { mvar_id } => Rendered::atom(format!("?{}", mvar_id.name)),
+            LeanExpr::Sort { u } => {
+                let sort = level_to_sort(u);
+                let prec = if sort.contains(' ') {
+                    MAX_PREC
+                } else {
+                    ATOM_PREC
+                };
+                Rendered::new(sort, prec)
+            }
+            LeanExpr::Const { decl_name, .. } => Rendered::atom(
+                constant_notation(&decl_name.to_string())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| decl_name.to_string()),
+            ),
+            LeanExpr::Lit(Literal::NatVal { val }) => Rendered::atom(val.to_string()),
+            LeanExpr::Lit(Literal::StrVal { val }) => Rendered::atom(format!("{val:?}")),
+            LeanExpr::App { .. } => self.render_app(expr),
+            LeanExpr::ForallE { .. } => self.render_forall(expr),
+            LeanExpr::Lam {
+                binder_name,
+                binder_type,
+                body,
+                binder_info,
+            } => {
+                let typ = self.render(binder_type).text;
+                let name = self.fresh(binder_name);
+                let (open, close) = binder_brackets(binder_info);
+                let body = self.with_binder(name.clone(), binder_info, |p| p.render(body).text);
+                Rendered::new(format!("fun {open}{name} : {typ}{close} => {body}"), 0)
+            }
+            LeanExpr::LetE {
+                decl_name,
+                typ,
+                value,
+                body,
+                ..
+            } => {
+                let typ = self.render(typ).text;
+                let value = self.render(value).text;
+                let name = self.fresh(decl_name);
+                let body =
+                    self.with_binder(name.clone(), &BinderInfo::Default, |p| p.render(body).text);
+                Rendered::new(format!("let {name} : {typ} := {value}; {body}"), 0)
+            }
+            LeanExpr::MData { expr, .. } => self.render(expr),
+            LeanExpr::Proj { idx, structure, .. } => {
+                let structure = self.render(structure).at(ARG_PREC);
+                Rendered::atom(format!("{structure}.{}", idx + 1))
+            }
+        }
+    }
+
+    fn render_app(&mut self, expr: &LeanExpr) -> Rendered {
+        let function = expr.get_app_fn();
+        let args = expr.get_app_args();
+
+        if let LeanExpr::Const { decl_name, .. } = function {
+            if let Some(rendered) = self.render_notation(&decl_name.to_string(), &args) {
+                return rendered;
+            }
+        }
+
+        let mut text = self.render(function).at(MAX_PREC);
+        for arg in args {
+            if self.is_instance(arg) {
+                continue;
+            }
+            text.push(' ');
+            text.push_str(&self.render(arg).at(ARG_PREC));
+        }
+        Rendered::new(text, MAX_PREC)
+    }
+
+    // render a fully applied notation, if the constant has one
+    fn render_notation(&mut self, name: &str, args: &[&LeanExpr]) -> Option<Rendered> {
+        if let Some((implicit, symbol, prec, lhs_prec, rhs_prec)) = infix_notation(name) {
+            let [lhs, rhs] = args.get(implicit..)? else {
+                return None;
+            };
+            let lhs = self.render(lhs).at(lhs_prec);
+            let rhs = self.render(rhs).at(rhs_prec);
+            return Some(Rendered::new(format!("{lhs} {symbol} {rhs}"), prec));
+        }
+        if let Some((implicit, symbol, postfix, prec, arg_prec)) = unary_notation(name) {
+            let [arg] = args.get(implicit..)? else {
+                return None;
+            };
+            let arg = self.render(arg).at(arg_prec);
+            let text = if postfix {
+                format!("{arg}{symbol}")
+            } else {
+                format!("{symbol}{arg}")
+            };
+            return Some(Rendered::new(text, prec));
+        }
+        match (name, args) {
+            ("OfNat.ofNat", [_, n, _]) => Some(self.render(n)),
+            ("OfScientific.ofScientific", [_, _, mantissa, sign, exponent]) => {
+                scientific(mantissa, sign, exponent).map(Rendered::atom)
+            }
+            _ => None,
+        }
+    }
+
+    fn render_forall(&mut self, expr: &LeanExpr) -> Rendered {
+        let LeanExpr::ForallE {
+            binder_name,
+            binder_type,
+            body,
+            binder_info,
+        } = expr
+        else {
+            unreachable!("render_forall is only called on ForallE");
+        };
+
+        // non-dependent explicit binders are implications
+        if *binder_info == BinderInfo::Default && !body.has_loose_bvar(0) {
+            let lhs = self.render(binder_type).at(26);
+            let rhs = self.with_binder(String::new(), binder_info, |p| p.render(body).at(25));
+            return Rendered::new(format!("{lhs} → {rhs}"), 25);
+        }
+
+        // collect a run of binders sharing a type and binder info into a single group
+        let typ = self.render(binder_type).text;
+        let mut group = vec![self.fresh(binder_name)];
+        self.push_binder(group[0].clone(), binder_info);
+        let mut body = &**body;
+        while let LeanExpr::ForallE {
+            binder_name: next_name,
+            binder_type: next_type,
+            body: next_body,
+            binder_info: next_info,
+        } = body
+        {
+            let dependent = *next_info != BinderInfo::Default || next_body.has_loose_bvar(0);
+            if !dependent
+                || next_info != binder_info
+                || next_type.has_loose_bvar(0)
+                || self.render(next_type).text != typ
+            {
+                break;
+            }
+            let name = self.fresh(next_name);
+            self.push_binder(name.clone(), binder_info);
+            group.push(name);
+            body = next_body;
+        }
+        let rest = self.render(body).text;
+        self.pop_binders(group.len());
+
+        let (open, close) = binder_brackets(binder_info);
+        Rendered::new(
+            format!("∀ {open}{} : {typ}{close}, {rest}", group.join(" ")),
+            0,
+        )
+    }
+}
+
+impl LeanExpr {
+    // render this expression as (approximate) Lean 4 surface syntax, eliding instance arguments
+    pub fn pretty(&self) -> String {
+        Printer {
+            names: Vec::new(),
+            instances: Vec::new(),
+        }
+        .render(self)
+        .text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lean_expr::{BinderInfo, LeanExpr, Level};
+
+    fn constant(name: &str) -> LeanExpr {
+        LeanExpr::Const {
+            decl_name: name.into(),
+            us: Vec::new(),
+        }
+    }
+
+    fn bvar(de_bruijn_index: u64) -> LeanExpr {
+        LeanExpr::BVar { de_bruijn_index }
+    }
+
+    fn forall(
+        name: &str,
+        binder_type: LeanExpr,
+        binder_info: BinderInfo,
+        body: LeanExpr,
+    ) -> LeanExpr {
+        LeanExpr::ForallE {
+            binder_name: name.into(),
+            binder_type: Box::new(binder_type),
+            body: Box::new(body),
+            binder_info,
+        }
+    }
+
+    #[test]
+    fn test_pretty_add_zero() {
+        let add_zero =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero.json"))
+                .unwrap();
+
+        assert_eq!(add_zero.pretty(), "∀ (a : ℚ), a + 0 = a");
+    }
+
+    #[test]
+    fn test_pretty_mul_comm() {
+        let mul_comm =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/mul_comm.json"))
+                .unwrap();
+
+        assert_eq!(mul_comm.pretty(), "∀ (a b : ℚ), a * b = b * a");
+    }
+
+    #[test]
+    fn test_pretty_hypotheses() {
+        let mul_inv_cancel =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/mul_inv_cancel.json"))
+                .unwrap();
+
+        assert_eq!(mul_inv_cancel.pretty(), "∀ (a : ℚ), a ≠ 0 → a * a⁻¹ = 1");
+    }
+
+    #[test]
+    fn test_pretty_normalization_artifacts() {
+        let add_zero_let =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero_let.json"))
+                .unwrap();
+        let add_zero_beta =
+            serde_json::from_str::<LeanExpr>(include_str!("../../test_assets/add_zero_beta.json"))
+                .unwrap();
+
+        assert_eq!(
+            add_zero_let.pretty(),
+            "∀ (a : ℚ), (let z : ℚ := 0; a + z) = a"
+        );
+        assert_eq!(
+            add_zero_beta.pretty(),
+            "∀ (a : ℚ), (fun (x : ℚ) => x + 0) a = a"
+        );
+    }
+
+    #[test]
+    fn test_pretty_instances() {
+        // ∀ {α : Type} [inst : DivisionRing α] (a : α),
+        //   @Div.div α (@DivInvMonoid.toDiv α (@DivisionRing.toDivInvMonoid α inst)) a a = 1
+        let div_self = |alpha: LeanExpr, inst: LeanExpr, a: LeanExpr| {
+            let div = LeanExpr::mk_app(
+                constant("DivInvMonoid.toDiv"),
+                [
+                    alpha.clone(),
+                    LeanExpr::mk_app(
+                        constant("DivisionRing.toDivInvMonoid"),
+                        [alpha.clone(), inst],
+                    ),
+                ],
+            );
+            LeanExpr::mk_app(
+                constant("Eq"),
+                [
+                    alpha.clone(),
+                    LeanExpr::mk_app(constant("Div.div"), [alpha, div, a.clone(), a]),
+                    constant("One.one"),
+                ],
+            )
+        };
+        let generic = forall(
+            "α",
+            LeanExpr::Sort {
+                u: Level::Succ(Box::new(Level::Zero)),
+            },
+            BinderInfo::Implicit,
+            forall(
+                "inst",
+                LeanExpr::mk_app(constant("DivisionRing"), [bvar(0)]),
+                BinderInfo::InstImplicit,
+                forall(
+                    "a",
+                    bvar(1),
+                    BinderInfo::Default,
+                    div_self(bvar(2), bvar(1), bvar(0)),
+                ),
+            ),
+        );
+
+        assert_eq!(
+            generic.pretty(),
+            "∀ {α : Type}, ∀ [inst : DivisionRing α], ∀ (a : α), Div.div α a a = One.one"
+        );
+
+        let rat = forall(
+            "a",
+            constant("Rat"),
+            BinderInfo::Default,
+            div_self(constant("Rat"), constant("Rat.instDivisionRing"), bvar(0)),
+        );
+
+        assert_eq!(rat.pretty(), "∀ (a : ℚ), Div.div ℚ a a = One.one");
+
+        // a `to*` function applied to something other than an instance is an ordinary argument
+        let to_nnreal = forall(
+            "a",
+            constant("Real"),
+            BinderInfo::Default,
+            LeanExpr::mk_app(
+                constant("Eq"),
+                [
+                    constant("NNReal"),
+                    LeanExpr::mk_app(
+                        constant("NNReal.sqrt"),
+                        [LeanExpr::mk_app(constant("Real.toNNReal"), [bvar(0)])],
+                    ),
+                    constant("One.one"),
+                ],
+            ),
+        );
+
+        assert_eq!(
+            to_nnreal.pretty(),
+            "∀ (a : ℝ), NNReal.sqrt (Real.toNNReal a) = One.one"
+        );
+    }
+}